@@ -0,0 +1,914 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_spl::token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
+use staking_program::program::StakingProgram;
+use staking_program::{
+    LockTierTable, StakePosition, StakingPool, UserStakeAccount, REWARD_PRECISION,
+    SHARE_PRICE_PRECISION,
+};
+
+declare_id!("vau1tCmpRNFBzRdzmKcJRwoqd1rGjv2VAAoMBHimge4");
+
+pub const MAX_HARVEST_BOUNTY_BPS: u16 = 500;
+
+#[program]
+pub mod auto_compound_vault {
+    use super::*;
+
+    /// Creates a vault that stakes into `tier_index` of a staking pool. The
+    /// authority seeds the vault position with `initial_deposit`; those shares
+    /// are never redeemable, which keeps the position above the pool minimum
+    /// and the share price from being manipulated while the vault is empty.
+    pub fn initialize_vault(
+        ctx: Context<InitializeVault>,
+        tier_index: u8,
+        harvest_bounty_bps: u16,
+        initial_deposit: u64,
+    ) -> Result<()> {
+        require!(
+            harvest_bounty_bps <= MAX_HARVEST_BOUNTY_BPS,
+            ErrorCode::InvalidBountyBps
+        );
+
+        let tier = ctx
+            .accounts
+            .lock_tier_table
+            .tiers
+            .get(tier_index as usize)
+            .ok_or(ErrorCode::LockedTier)?;
+        require!(tier.lock_duration == 0, ErrorCode::LockedTier);
+
+        let vault_key = ctx.accounts.vault.key();
+        let authority_seeds = &[
+            b"vault_authority",
+            vault_key.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let signer = &[&authority_seeds[..]];
+
+        // The vault authority is the staker of record and pays for the
+        // staking accounts it opens, so it has to hold their rent.
+        let rent = Rent::get()?;
+        let lamports = rent.minimum_balance(8 + UserStakeAccount::INIT_SPACE)
+            + rent.minimum_balance(8 + StakePosition::INIT_SPACE);
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.authority.to_account_info(),
+            to: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.system_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        system_program::transfer(cpi_ctx, lamports)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.authority_token_account.to_account_info(),
+            to: ctx.accounts.vault_token_account.to_account_info(),
+            authority: ctx.accounts.authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, initial_deposit)?;
+
+        let cpi_accounts = staking_program::cpi::accounts::InitializeUserStake {
+            staking_pool: ctx.accounts.staking_pool.to_account_info(),
+            user_stake_account: ctx.accounts.user_stake_account.to_account_info(),
+            user: ctx.accounts.vault_authority.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.staking_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        staking_program::cpi::initialize_user_stake(cpi_ctx)?;
+
+        let cpi_accounts = staking_program::cpi::accounts::StakeTokens {
+            staking_pool: ctx.accounts.staking_pool.to_account_info(),
+            lock_tier_table: ctx.accounts.lock_tier_table.to_account_info(),
+            stake_vault: ctx.accounts.stake_vault.to_account_info(),
+            user_stake_account: ctx.accounts.user_stake_account.to_account_info(),
+            stake_position: ctx.accounts.stake_position.to_account_info(),
+            user: ctx.accounts.vault_authority.to_account_info(),
+            user_token_account: ctx.accounts.vault_token_account.to_account_info(),
+            position_mint: None,
+            position_token_account: None,
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.staking_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        staking_program::cpi::stake_tokens(cpi_ctx, initial_deposit, tier_index, false)?;
+
+        let vault = &mut ctx.accounts.vault;
+        vault.authority = ctx.accounts.authority.key();
+        vault.staking_pool = ctx.accounts.staking_pool.key();
+        vault.stake_position = ctx.accounts.stake_position.key();
+        vault.vault_token_account = ctx.accounts.vault_token_account.key();
+        vault.share_mint = ctx.accounts.share_mint.key();
+        vault.harvest_bounty_bps = harvest_bounty_bps;
+        vault.locked_shares = initial_deposit;
+        vault.total_harvested = 0;
+        vault.last_harvest_time = 0;
+        vault.bump = ctx.bumps.vault;
+        vault.authority_bump = ctx.bumps.vault_authority;
+
+        emit!(VaultInitialized {
+            vault: vault.key(),
+            staking_pool: vault.staking_pool,
+            tier_index,
+            harvest_bounty_bps,
+            initial_deposit,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_harvest_bounty(ctx: Context<UpdateVault>, harvest_bounty_bps: u16) -> Result<()> {
+        require!(
+            harvest_bounty_bps <= MAX_HARVEST_BOUNTY_BPS,
+            ErrorCode::InvalidBountyBps
+        );
+
+        let vault = &mut ctx.accounts.vault;
+        vault.harvest_bounty_bps = harvest_bounty_bps;
+
+        emit!(HarvestBountyUpdated {
+            vault: vault.key(),
+            harvest_bounty_bps,
+        });
+
+        Ok(())
+    }
+
+    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let vault = &ctx.accounts.vault;
+        let total_shares = ctx.accounts.share_mint.supply + vault.locked_shares;
+        let total_assets = total_assets(
+            vault,
+            &ctx.accounts.staking_pool,
+            &ctx.accounts.stake_position,
+            ctx.accounts.reward_vault.amount,
+        )?;
+        let shares = convert(amount, total_shares, total_assets)?;
+        require!(shares > 0, ErrorCode::InvalidAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.vault_token_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let vault_key = vault.key();
+        let authority_seeds = &[b"vault_authority", vault_key.as_ref(), &[vault.authority_bump]];
+        let signer = &[&authority_seeds[..]];
+
+        let cpi_accounts = staking_program::cpi::accounts::IncreaseStake {
+            staking_pool: ctx.accounts.staking_pool.to_account_info(),
+            lock_tier_table: ctx.accounts.lock_tier_table.to_account_info(),
+            stake_vault: ctx.accounts.stake_vault.to_account_info(),
+            user_stake_account: ctx.accounts.user_stake_account.to_account_info(),
+            stake_position: ctx.accounts.stake_position.to_account_info(),
+            user: ctx.accounts.vault_authority.to_account_info(),
+            position_token_account: None,
+            user_token_account: ctx.accounts.vault_token_account.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.staking_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        staking_program::cpi::increase_stake(cpi_ctx, amount)?;
+
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            to: ctx.accounts.user_share_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::mint_to(cpi_ctx, shares)?;
+
+        emit!(VaultDeposited {
+            vault: vault_key,
+            user: ctx.accounts.user.key(),
+            amount,
+            shares,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless crank that claims the vault position's rewards and
+    /// stakes them back in, paying the caller `harvest_bounty_bps` of the
+    /// harvest.
+    pub fn harvest(ctx: Context<Harvest>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let vault_key = vault.key();
+        let authority_seeds = &[b"vault_authority", vault_key.as_ref(), &[vault.authority_bump]];
+        let signer = &[&authority_seeds[..]];
+
+        let balance_before = ctx.accounts.vault_token_account.amount;
+
+        let cpi_accounts = staking_program::cpi::accounts::ClaimStakingRewards {
+            staking_pool: ctx.accounts.staking_pool.to_account_info(),
+            reward_vault: ctx.accounts.reward_vault.to_account_info(),
+            stake_position: ctx.accounts.stake_position.to_account_info(),
+            user: ctx.accounts.vault_authority.to_account_info(),
+            position_token_account: None,
+            user_token_account: ctx.accounts.vault_token_account.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.staking_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        staking_program::cpi::claim_staking_rewards(cpi_ctx)?;
+
+        ctx.accounts.vault_token_account.reload()?;
+        let harvested = ctx
+            .accounts
+            .vault_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let bounty = (harvested as u128 * vault.harvest_bounty_bps as u128 / 10000) as u64;
+        let restaked = harvested - bounty;
+
+        if bounty > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.vault_token_account.to_account_info(),
+                to: ctx.accounts.caller_token_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, bounty)?;
+        }
+
+        if restaked > 0 {
+            let cpi_accounts = staking_program::cpi::accounts::IncreaseStake {
+                staking_pool: ctx.accounts.staking_pool.to_account_info(),
+                lock_tier_table: ctx.accounts.lock_tier_table.to_account_info(),
+                stake_vault: ctx.accounts.stake_vault.to_account_info(),
+                user_stake_account: ctx.accounts.user_stake_account.to_account_info(),
+                stake_position: ctx.accounts.stake_position.to_account_info(),
+                user: ctx.accounts.vault_authority.to_account_info(),
+                position_token_account: None,
+                user_token_account: ctx.accounts.vault_token_account.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.staking_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            staking_program::cpi::increase_stake(cpi_ctx, restaked)?;
+        }
+
+        let clock = Clock::get()?;
+        let vault = &mut ctx.accounts.vault;
+        vault.total_harvested += harvested;
+        vault.last_harvest_time = clock.unix_timestamp;
+
+        emit!(VaultHarvested {
+            vault: vault_key,
+            caller: ctx.accounts.caller.key(),
+            harvested,
+            bounty,
+            restaked,
+        });
+
+        Ok(())
+    }
+
+    /// Burns `shares` and splits their value off the vault position into an
+    /// unbonding position, paid out by `complete_withdrawal` once the pool's
+    /// unbonding period ends. The user covers the new position's rent and gets
+    /// it back on completion.
+    pub fn request_withdrawal(ctx: Context<RequestWithdrawal>, shares: u64) -> Result<()> {
+        require!(shares > 0, ErrorCode::InvalidAmount);
+
+        let vault = &ctx.accounts.vault;
+        let total_shares = ctx.accounts.share_mint.supply + vault.locked_shares;
+        let total_assets = total_assets(
+            vault,
+            &ctx.accounts.staking_pool,
+            &ctx.accounts.stake_position,
+            ctx.accounts.reward_vault.amount,
+        )?;
+        let amount = convert(shares, total_assets, total_shares)?;
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            from: ctx.accounts.user_share_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::burn(cpi_ctx, shares)?;
+
+        let rent = Rent::get()?;
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.user.to_account_info(),
+            to: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.system_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        system_program::transfer(cpi_ctx, rent.minimum_balance(8 + StakePosition::INIT_SPACE))?;
+
+        let vault_key = vault.key();
+        let authority_seeds = &[b"vault_authority", vault_key.as_ref(), &[vault.authority_bump]];
+        let signer = &[&authority_seeds[..]];
+
+        let position_index = ctx.accounts.user_stake_account.position_count;
+
+        let cpi_accounts = staking_program::cpi::accounts::InitiatePartialUnstake {
+            staking_pool: ctx.accounts.staking_pool.to_account_info(),
+            user_stake_account: ctx.accounts.user_stake_account.to_account_info(),
+            stake_position: ctx.accounts.stake_position.to_account_info(),
+            unbonding_position: ctx.accounts.unbonding_position.to_account_info(),
+            user: ctx.accounts.vault_authority.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.staking_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        staking_program::cpi::initiate_partial_unstake(cpi_ctx, amount)?;
+
+        let withdrawal_ticket = &mut ctx.accounts.withdrawal_ticket;
+        withdrawal_ticket.vault = vault_key;
+        withdrawal_ticket.owner = ctx.accounts.user.key();
+        withdrawal_ticket.position_index = position_index;
+        withdrawal_ticket.shares = shares;
+        withdrawal_ticket.amount = amount;
+        withdrawal_ticket.bump = ctx.bumps.withdrawal_ticket;
+
+        emit!(WithdrawalRequested {
+            vault: vault_key,
+            user: ctx.accounts.user.key(),
+            ticket: withdrawal_ticket.key(),
+            shares,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn complete_withdrawal(ctx: Context<CompleteWithdrawal>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let vault_key = vault.key();
+        let authority_seeds = &[b"vault_authority", vault_key.as_ref(), &[vault.authority_bump]];
+        let signer = &[&authority_seeds[..]];
+
+        let balance_before = ctx.accounts.vault_token_account.amount;
+        let lamports_before = ctx.accounts.vault_authority.lamports();
+
+        let cpi_accounts = staking_program::cpi::accounts::CompleteUnstake {
+            staking_pool: ctx.accounts.staking_pool.to_account_info(),
+            stake_vault: ctx.accounts.stake_vault.to_account_info(),
+            reward_vault: ctx.accounts.reward_vault.to_account_info(),
+            lock_tier_table: ctx.accounts.lock_tier_table.to_account_info(),
+            user_stake_account: ctx.accounts.user_stake_account.to_account_info(),
+            stake_position: ctx.accounts.unbonding_position.to_account_info(),
+            user: ctx.accounts.vault_authority.to_account_info(),
+            position_mint: None,
+            position_token_account: None,
+            user_token_account: ctx.accounts.vault_token_account.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.staking_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        staking_program::cpi::complete_unstake(cpi_ctx)?;
+
+        // Pay out what actually arrived, which is lower than the ticket amount
+        // if the pool was slashed during unbonding.
+        ctx.accounts.vault_token_account.reload()?;
+        let amount = ctx
+            .accounts
+            .vault_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let refund = ctx
+            .accounts
+            .vault_authority
+            .lamports()
+            .checked_sub(lamports_before)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.vault_token_account.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        if refund > 0 {
+            let cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.vault_authority.to_account_info(),
+                to: ctx.accounts.user.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.system_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            system_program::transfer(cpi_ctx, refund)?;
+        }
+
+        emit!(WithdrawalCompleted {
+            vault: vault_key,
+            user: ctx.accounts.user.key(),
+            ticket: ctx.accounts.withdrawal_ticket.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeVault<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Vault::INIT_SPACE,
+        seeds = [b"vault", staking_pool.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: System-owned PDA that stakes on the vault's behalf.
+    #[account(mut, seeds = [b"vault_authority", vault.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"vault_share_mint", vault.key().as_ref()],
+        bump,
+        mint::decimals = stake_mint.decimals,
+        mint::authority = vault_authority,
+    )]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stake_mint,
+        token::authority = vault_authority,
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(address = staking_pool.stake_mint)]
+    pub stake_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut)]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    /// CHECK: Pinned to the pool's stake vault.
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the staking program.
+    #[account(mut)]
+    pub user_stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: Created by the staking program.
+    #[account(mut)]
+    pub stake_position: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub authority_token_account: Account<'info, TokenAccount>,
+
+    pub staking_program: Program<'info, StakingProgram>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.staking_pool.as_ref()],
+        bump = vault.bump,
+        has_one = authority
+    )]
+    pub vault: Account<'info, Vault>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(
+        seeds = [b"vault", vault.staking_pool.as_ref()],
+        bump = vault.bump,
+        has_one = staking_pool,
+        has_one = stake_position,
+        has_one = vault_token_account,
+        has_one = share_mint
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Signs for the vault through its seeds.
+    #[account(seeds = [b"vault_authority", vault.key().as_ref()], bump = vault.authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut)]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    /// CHECK: Pinned to the pool's stake vault.
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: UncheckedAccount<'info>,
+
+    #[account(address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    /// CHECK: Validated by the staking program.
+    #[account(mut)]
+    pub user_stake_account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub stake_position: Account<'info, StakePosition>,
+
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = share_mint)]
+    pub user_share_account: Account<'info, TokenAccount>,
+
+    pub staking_program: Program<'info, StakingProgram>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Harvest<'info> {
+    #[account(
+        mut,
+        seeds = [b"vault", vault.staking_pool.as_ref()],
+        bump = vault.bump,
+        has_one = staking_pool,
+        has_one = stake_position,
+        has_one = vault_token_account
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Signs for the vault through its seeds.
+    #[account(seeds = [b"vault_authority", vault.key().as_ref()], bump = vault.authority_bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut)]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    /// CHECK: Pinned to the pool's stake vault.
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Pinned to the pool's reward vault.
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the staking program.
+    #[account(mut)]
+    pub user_stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: Validated by the staking program.
+    #[account(mut)]
+    pub stake_position: UncheckedAccount<'info>,
+
+    pub caller: Signer<'info>,
+
+    #[account(mut)]
+    pub caller_token_account: Account<'info, TokenAccount>,
+
+    pub staking_program: Program<'info, StakingProgram>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RequestWithdrawal<'info> {
+    #[account(
+        seeds = [b"vault", vault.staking_pool.as_ref()],
+        bump = vault.bump,
+        has_one = staking_pool,
+        has_one = stake_position,
+        has_one = share_mint
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Signs for the vault through its seeds.
+    #[account(
+        mut,
+        seeds = [b"vault_authority", vault.key().as_ref()],
+        bump = vault.authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub share_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + WithdrawalTicket::INIT_SPACE,
+        seeds = [
+            b"withdrawal",
+            vault.key().as_ref(),
+            &user_stake_account.position_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub withdrawal_ticket: Account<'info, WithdrawalTicket>,
+
+    #[account(mut)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    #[account(mut)]
+    pub stake_position: Account<'info, StakePosition>,
+
+    /// CHECK: Created by the staking program.
+    #[account(mut)]
+    pub unbonding_position: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut, token::mint = share_mint)]
+    pub user_share_account: Account<'info, TokenAccount>,
+
+    pub staking_program: Program<'info, StakingProgram>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteWithdrawal<'info> {
+    #[account(
+        seeds = [b"vault", vault.staking_pool.as_ref()],
+        bump = vault.bump,
+        has_one = staking_pool,
+        has_one = vault_token_account
+    )]
+    pub vault: Account<'info, Vault>,
+
+    /// CHECK: Signs for the vault through its seeds.
+    #[account(
+        mut,
+        seeds = [b"vault_authority", vault.key().as_ref()],
+        bump = vault.authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"withdrawal",
+            vault.key().as_ref(),
+            &withdrawal_ticket.position_index.to_le_bytes()
+        ],
+        bump = withdrawal_ticket.bump,
+        has_one = vault,
+        constraint = withdrawal_ticket.owner == user.key() @ ErrorCode::NotTicketOwner,
+        close = user
+    )]
+    pub withdrawal_ticket: Account<'info, WithdrawalTicket>,
+
+    #[account(mut)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    /// CHECK: Pinned to the pool's stake vault.
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Pinned to the pool's reward vault.
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    /// CHECK: Validated by the staking program.
+    #[account(mut)]
+    pub user_stake_account: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = unbonding_position.index == withdrawal_ticket.position_index
+            @ ErrorCode::PositionMismatch
+    )]
+    pub unbonding_position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub staking_program: Program<'info, StakingProgram>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Vault {
+    pub authority: Pubkey,
+    pub staking_pool: Pubkey,
+    pub stake_position: Pubkey,
+    pub vault_token_account: Pubkey,
+    pub share_mint: Pubkey,
+    pub harvest_bounty_bps: u16,
+    pub locked_shares: u64,
+    pub total_harvested: u64,
+    pub last_harvest_time: i64,
+    pub bump: u8,
+    pub authority_bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct WithdrawalTicket {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub position_index: u64,
+    pub shares: u64,
+    pub amount: u64,
+    pub bump: u8,
+}
+
+#[event]
+pub struct VaultInitialized {
+    pub vault: Pubkey,
+    pub staking_pool: Pubkey,
+    pub tier_index: u8,
+    pub harvest_bounty_bps: u16,
+    pub initial_deposit: u64,
+}
+
+#[event]
+pub struct HarvestBountyUpdated {
+    pub vault: Pubkey,
+    pub harvest_bounty_bps: u16,
+}
+
+#[event]
+pub struct VaultDeposited {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+}
+
+#[event]
+pub struct VaultHarvested {
+    pub vault: Pubkey,
+    pub caller: Pubkey,
+    pub harvested: u64,
+    pub bounty: u64,
+    pub restaked: u64,
+}
+
+#[event]
+pub struct WithdrawalRequested {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub ticket: Pubkey,
+    pub shares: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct WithdrawalCompleted {
+    pub vault: Pubkey,
+    pub user: Pubkey,
+    pub ticket: Pubkey,
+    pub amount: u64,
+}
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("Harvest bounty exceeds the maximum")]
+    InvalidBountyBps,
+    #[msg("Vault tier must not have a lock period")]
+    LockedTier,
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Signer does not own this withdrawal ticket")]
+    NotTicketOwner,
+    #[msg("Position does not match the withdrawal ticket")]
+    PositionMismatch,
+    #[msg("Arithmetic overflow in calculation")]
+    ArithmeticOverflow,
+}
+
+/// Everything the vault's shares are backed by: the staking position plus its
+/// unharvested rewards, including reward vault tokens the pool has not synced
+/// yet, net of the harvest bounty. Pricing on the position alone would let a
+/// depositor harvest yield that accrued before they joined.
+fn total_assets(
+    vault: &Vault,
+    pool: &StakingPool,
+    position: &StakePosition,
+    reward_vault_balance: u64,
+) -> Result<u64> {
+    let rewards = pending_rewards(pool, position, reward_vault_balance)?;
+    let bounty = (rewards as u128 * vault.harvest_bounty_bps as u128 / 10000) as u64;
+
+    position_value(pool, position)?
+        .checked_add(rewards - bounty)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Rewards `claim_staking_rewards` would pay the position after a sync.
+fn pending_rewards(
+    pool: &StakingPool,
+    position: &StakePosition,
+    reward_vault_balance: u64,
+) -> Result<u64> {
+    let mut acc_reward_per_weight = pool.acc_reward_per_weight;
+    let undistributed = reward_vault_balance.saturating_sub(pool.reward_balance);
+    if undistributed > 0 && pool.total_weighted_stake > 0 {
+        acc_reward_per_weight = acc_reward_per_weight
+            .checked_add(undistributed as u128 * REWARD_PRECISION / pool.total_weighted_stake)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+    }
+
+    let weight = position.shares as u128 * position.reward_multiplier as u128;
+    let accrued = weight
+        .checked_mul(acc_reward_per_weight)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / REWARD_PRECISION;
+    let accrued = u64::try_from(accrued.saturating_sub(position.reward_debt))
+        .map_err(|_| error!(ErrorCode::ArithmeticOverflow))?;
+
+    position
+        .pending_rewards
+        .checked_add(accrued)
+        .ok_or(error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Token value of the vault's staking position, excluding unharvested rewards.
+fn position_value(pool: &StakingPool, position: &StakePosition) -> Result<u64> {
+    let value = (position.shares as u128)
+        .checked_mul(pool.share_price)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / SHARE_PRICE_PRECISION;
+
+    u64::try_from(value).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Scales `amount` by `numerator / denominator`, rounding down.
+fn convert(amount: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    require!(denominator > 0, ErrorCode::InvalidAmount);
+
+    let converted = (amount as u128)
+        .checked_mul(numerator as u128)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / denominator as u128;
+
+    u64::try_from(converted).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
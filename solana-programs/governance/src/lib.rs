@@ -1,7 +1,17 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_lang::{system_program, Discriminator};
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount, Transfer};
+use rewards_program::program::RewardsProgram;
+use rewards_program::RewardsPool;
+use staking_program::program::StakingProgram;
+use staking_program::{PoolState, StakingPool};
 
-declare_id!("govnNQF7Z8k9pVwJLzY4XpKj6h1rGE3tqTmE9xKvPp");
+declare_id!("govnNQF7Z8k9pVwJLzY4XpKj6h1rGE3tqTmE9xKvPp1");
+
+pub const MAX_EXCLUDED_ACCOUNTS: usize = 10;
+pub const MAX_COUNCIL_MEMBERS: usize = 9;
+pub const MAX_ACTIVE_COUNCIL_ACTIONS: usize = 8;
+pub const PROPOSAL_TYPE_COUNT: usize = 5;
 
 #[program]
 pub mod governance_program {
@@ -13,13 +23,21 @@ pub mod governance_program {
         min_voting_power: u64,
         quorum_percentage: u64,
     ) -> Result<()> {
+        require!(quorum_percentage <= 100, ErrorCode::InvalidQuorumPercentage);
+
         let governance = &mut ctx.accounts.governance;
         governance.authority = ctx.accounts.authority.key();
         governance.governance_token = ctx.accounts.governance_token.key();
         governance.voting_period = voting_period;
         governance.min_voting_power = min_voting_power;
-        governance.quorum_percentage = quorum_percentage;
+        governance.proposal_rules = [ProposalRules {
+            voting_rule: VotingRule::Standard,
+            quorum_percentage,
+            veto_threshold_percentage: 0,
+        }; PROPOSAL_TYPE_COUNT];
+        governance.voting_weight_curve = VotingWeightCurve::Linear;
         governance.proposal_count = 0;
+        governance.excluded_accounts = Vec::new();
         governance.bump = ctx.bumps.governance;
 
         emit!(GovernanceInitialized {
@@ -31,6 +49,166 @@ pub mod governance_program {
         Ok(())
     }
 
+    pub fn set_excluded_accounts(
+        ctx: Context<SetExcludedAccounts>,
+        excluded_accounts: Vec<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            excluded_accounts.len() <= MAX_EXCLUDED_ACCOUNTS,
+            ErrorCode::TooManyExcludedAccounts
+        );
+        for (i, account) in excluded_accounts.iter().enumerate() {
+            require!(
+                !excluded_accounts[..i].contains(account),
+                ErrorCode::DuplicateExcludedAccount
+            );
+        }
+
+        let governance = &mut ctx.accounts.governance;
+        governance.excluded_accounts = excluded_accounts;
+
+        emit!(ExcludedAccountsUpdated {
+            governance: governance.key(),
+            excluded_accounts: governance.excluded_accounts.clone(),
+        });
+
+        Ok(())
+    }
+
+    pub fn set_proposal_rules(
+        ctx: Context<SetProposalRules>,
+        proposal_type: ProposalType,
+        rules: ProposalRules,
+    ) -> Result<()> {
+        require!(rules.quorum_percentage <= 100, ErrorCode::InvalidQuorumPercentage);
+        if rules.voting_rule == VotingRule::Optimistic {
+            require!(
+                proposal_type == ProposalType::ParameterChange,
+                ErrorCode::OptimisticNotAllowed
+            );
+            require!(
+                rules.veto_threshold_percentage > 0 && rules.veto_threshold_percentage <= 100,
+                ErrorCode::InvalidVetoThreshold
+            );
+        }
+
+        let governance = &mut ctx.accounts.governance;
+        governance.proposal_rules[proposal_type as usize] = rules;
+
+        emit!(ProposalRulesUpdated {
+            governance: governance.key(),
+            proposal_type,
+            voting_rule: rules.voting_rule,
+            quorum_percentage: rules.quorum_percentage,
+            veto_threshold_percentage: rules.veto_threshold_percentage,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_voting_weight_curve(
+        ctx: Context<SetVotingWeightCurve>,
+        voting_weight_curve: VotingWeightCurve,
+    ) -> Result<()> {
+        if let VotingWeightCurve::Conviction { max_lock_duration, .. } = voting_weight_curve {
+            require!(max_lock_duration > 0, ErrorCode::InvalidLockDuration);
+        }
+
+        let governance = &mut ctx.accounts.governance;
+        governance.voting_weight_curve = voting_weight_curve;
+
+        emit!(VotingWeightCurveUpdated {
+            governance: governance.key(),
+            voting_weight_curve,
+        });
+
+        Ok(())
+    }
+
+    pub fn lock_voting_tokens(
+        ctx: Context<LockVotingTokens>,
+        amount: u64,
+        lock_duration: i64,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidLockAmount);
+        require!(lock_duration > 0, ErrorCode::InvalidLockDuration);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.voter_token_account.to_account_info(),
+            to: ctx.accounts.lock_vault.to_account_info(),
+            authority: ctx.accounts.voter.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let clock = Clock::get()?;
+        let vote_lock = &mut ctx.accounts.vote_lock;
+        vote_lock.governance = ctx.accounts.governance.key();
+        vote_lock.voter = ctx.accounts.voter.key();
+        vote_lock.amount = amount;
+        vote_lock.lock_start = clock.unix_timestamp;
+        vote_lock.lock_end = clock
+            .unix_timestamp
+            .checked_add(lock_duration)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        vote_lock.bump = ctx.bumps.vote_lock;
+
+        emit!(VotingTokensLocked {
+            voter: vote_lock.voter,
+            amount,
+            lock_end: vote_lock.lock_end,
+        });
+
+        Ok(())
+    }
+
+    pub fn unlock_voting_tokens(ctx: Context<UnlockVotingTokens>) -> Result<()> {
+        let vote_lock = &ctx.accounts.vote_lock;
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= vote_lock.lock_end,
+            ErrorCode::VoteLockNotExpired
+        );
+
+        let seeds = &[
+            b"vote_lock",
+            vote_lock.governance.as_ref(),
+            vote_lock.voter.as_ref(),
+            &[vote_lock.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lock_vault.to_account_info(),
+            to: ctx.accounts.voter_token_account.to_account_info(),
+            authority: vote_lock.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, vote_lock.amount)?;
+
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.lock_vault.to_account_info(),
+            destination: ctx.accounts.voter.to_account_info(),
+            authority: vote_lock.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::close_account(cpi_ctx)?;
+
+        emit!(VotingTokensUnlocked {
+            voter: vote_lock.voter,
+            amount: vote_lock.amount,
+        });
+
+        Ok(())
+    }
+
     pub fn create_proposal(
         ctx: Context<CreateProposal>,
         title: String,
@@ -45,6 +223,21 @@ pub mod governance_program {
             ErrorCode::InsufficientVotingPower
         );
 
+        let council_action = if proposal_type == ProposalType::CouncilRatification {
+            let council_action = ctx
+                .accounts
+                .council_action
+                .as_ref()
+                .ok_or(ErrorCode::MissingCouncilAction)?;
+            require!(
+                council_action.status == CouncilActionStatus::Active,
+                ErrorCode::CouncilActionNotActive
+            );
+            council_action.key()
+        } else {
+            Pubkey::default()
+        };
+
         let clock = Clock::get()?;
         let proposal = &mut ctx.accounts.proposal;
         proposal.governance = governance.key();
@@ -56,8 +249,12 @@ pub mod governance_program {
         proposal.end_time = clock.unix_timestamp + governance.voting_period;
         proposal.yes_votes = 0;
         proposal.no_votes = 0;
+        proposal.raw_yes_votes = 0;
+        proposal.raw_no_votes = 0;
+        proposal.voting_weight_curve = governance.voting_weight_curve;
         proposal.status = ProposalStatus::Active;
         proposal.executed = false;
+        proposal.council_action = council_action;
         proposal.bump = ctx.bumps.proposal;
 
         let governance = &mut ctx.accounts.governance;
@@ -89,21 +286,55 @@ pub mod governance_program {
             ErrorCode::VotingPeriodEnded
         );
 
-        let voter_account = &ctx.accounts.voter_account;
-        let voting_power = voter_account.amount;
+        let (voting_power, committed_duration) = match proposal.voting_weight_curve {
+            VotingWeightCurve::Conviction { .. } => {
+                let vote_lock = ctx
+                    .accounts
+                    .vote_lock
+                    .as_ref()
+                    .ok_or(ErrorCode::MissingVoteLock)?;
+                require!(
+                    vote_lock.lock_end >= proposal.end_time,
+                    ErrorCode::VoteLockTooShort
+                );
+                (vote_lock.amount, vote_lock.lock_end - vote_lock.lock_start)
+            }
+            _ => (ctx.accounts.voter_account.amount, 0),
+        };
+        let effective_weight =
+            effective_weight(proposal.voting_weight_curve, voting_power, committed_duration)?;
 
         let vote_record = &mut ctx.accounts.vote_record;
         vote_record.proposal = proposal.key();
         vote_record.voter = ctx.accounts.voter.key();
         vote_record.vote = vote;
         vote_record.voting_power = voting_power;
+        vote_record.effective_weight = effective_weight;
         vote_record.timestamp = clock.unix_timestamp;
         vote_record.bump = ctx.bumps.vote_record;
 
         let proposal = &mut ctx.accounts.proposal;
         match vote {
-            Vote::Yes => proposal.yes_votes += voting_power,
-            Vote::No => proposal.no_votes += voting_power,
+            Vote::Yes => {
+                proposal.yes_votes = proposal
+                    .yes_votes
+                    .checked_add(effective_weight)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                proposal.raw_yes_votes = proposal
+                    .raw_yes_votes
+                    .checked_add(voting_power)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+            }
+            Vote::No => {
+                proposal.no_votes = proposal
+                    .no_votes
+                    .checked_add(effective_weight)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                proposal.raw_no_votes = proposal
+                    .raw_no_votes
+                    .checked_add(voting_power)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+            }
         }
 
         emit!(VoteCast {
@@ -111,6 +342,7 @@ pub mod governance_program {
             voter: vote_record.voter,
             vote,
             voting_power,
+            effective_weight,
         });
 
         Ok(())
@@ -130,13 +362,42 @@ pub mod governance_program {
         );
 
         let governance = &ctx.accounts.governance;
-        let total_votes = proposal.yes_votes + proposal.no_votes;
-        let total_supply = ctx.accounts.governance_token.supply;
+        let excluded_supply = excluded_supply(governance, ctx.remaining_accounts)?;
+        let circulating_supply = ctx
+            .accounts
+            .governance_token
+            .supply
+            .checked_sub(excluded_supply)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
 
-        let quorum_met = (total_votes * 100) >= (total_supply * governance.quorum_percentage);
-        let passed = proposal.yes_votes > proposal.no_votes;
+        let rules = governance.proposal_rules[proposal.proposal_type as usize];
+        let passed = match rules.voting_rule {
+            VotingRule::Standard => {
+                let total_votes = (proposal.raw_yes_votes as u128)
+                    .checked_add(proposal.raw_no_votes as u128)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?;
+                let quorum_met = total_votes
+                    .checked_mul(100)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?
+                    >= (circulating_supply as u128)
+                        .checked_mul(rules.quorum_percentage as u128)
+                        .ok_or(ErrorCode::ArithmeticOverflow)?;
 
-        proposal.status = if quorum_met && passed {
+                quorum_met && proposal.yes_votes > proposal.no_votes
+            }
+            VotingRule::Optimistic => {
+                let vetoed = (proposal.raw_no_votes as u128)
+                    .checked_mul(100)
+                    .ok_or(ErrorCode::ArithmeticOverflow)?
+                    >= (circulating_supply as u128)
+                        .checked_mul(rules.veto_threshold_percentage as u128)
+                        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+                !vetoed
+            }
+        };
+
+        proposal.status = if passed {
             ProposalStatus::Passed
         } else {
             ProposalStatus::Rejected
@@ -147,6 +408,8 @@ pub mod governance_program {
             status: proposal.status,
             yes_votes: proposal.yes_votes,
             no_votes: proposal.no_votes,
+            circulating_supply,
+            voting_rule: rules.voting_rule,
         });
 
         Ok(())
@@ -169,6 +432,462 @@ pub mod governance_program {
 
         Ok(())
     }
+
+    pub fn initialize_council(
+        ctx: Context<InitializeCouncil>,
+        members: Vec<Pubkey>,
+        threshold: u8,
+        action_duration: i64,
+    ) -> Result<()> {
+        validate_council(&members, threshold)?;
+        require!(
+            action_duration > ctx.accounts.governance.voting_period,
+            ErrorCode::InvalidActionDuration
+        );
+
+        let council = &mut ctx.accounts.council;
+        council.governance = ctx.accounts.governance.key();
+        council.members = members;
+        council.threshold = threshold;
+        council.action_duration = action_duration;
+        council.action_count = 0;
+        council.active_targets = Vec::new();
+        council.bump = ctx.bumps.council;
+
+        emit!(CouncilUpdated {
+            council: council.key(),
+            members: council.members.clone(),
+            threshold,
+            action_duration,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_council(
+        ctx: Context<UpdateCouncil>,
+        members: Vec<Pubkey>,
+        threshold: u8,
+        action_duration: i64,
+    ) -> Result<()> {
+        validate_council(&members, threshold)?;
+        require!(
+            action_duration > ctx.accounts.governance.voting_period,
+            ErrorCode::InvalidActionDuration
+        );
+
+        let council = &mut ctx.accounts.council;
+        council.members = members;
+        council.threshold = threshold;
+        council.action_duration = action_duration;
+
+        emit!(CouncilUpdated {
+            council: council.key(),
+            members: council.members.clone(),
+            threshold,
+            action_duration,
+        });
+
+        Ok(())
+    }
+
+    /// The council PDA must be set as the staking pool's guardian.
+    pub fn council_pause_staking(ctx: Context<CouncilPauseStaking>, pause: bool) -> Result<()> {
+        let council = &ctx.accounts.council;
+        let approvers = collect_council_approvals(council, ctx.remaining_accounts)?;
+
+        // The council only toggles between Active and Paused; it must never
+        // lift an Emergency or touch a sunset pool.
+        let previous_state = ctx.accounts.staking_pool.state;
+        require!(
+            previous_state == PoolState::Active || previous_state == PoolState::Paused,
+            ErrorCode::PoolStateNotTogglable
+        );
+        require!(
+            previous_state != pool_state_for(pause),
+            ErrorCode::PauseStateUnchanged
+        );
+
+        let seeds = &[b"council", council.governance.as_ref(), &[council.bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = staking_program::cpi::accounts::SetPoolState {
+            staking_pool: ctx.accounts.staking_pool.to_account_info(),
+            authority: council.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.staking_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        staking_program::cpi::set_pool_state(cpi_ctx, pool_state_for(pause))?;
+
+        let clock = Clock::get()?;
+        let council_action = &mut ctx.accounts.council_action;
+        council_action.council = council.key();
+        council_action.index = council.action_count;
+        council_action.kind = CouncilActionKind::StakingPause;
+        council_action.target = ctx.accounts.staking_pool.key();
+        council_action.pause = pause;
+        council_action.previous_pause = previous_state == PoolState::Paused;
+        council_action.approvers = approvers;
+        council_action.executed_at = clock.unix_timestamp;
+        council_action.expires_at = clock
+            .unix_timestamp
+            .checked_add(council.action_duration)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        council_action.status = CouncilActionStatus::Active;
+        council_action.bump = ctx.bumps.council_action;
+
+        let council = &mut ctx.accounts.council;
+        claim_council_target(council, council_action.target)?;
+        council.action_count += 1;
+
+        emit!(CouncilActionExecuted {
+            council: council.key(),
+            council_action: council_action.key(),
+            kind: council_action.kind,
+            target: council_action.target,
+            pause,
+            expires_at: council_action.expires_at,
+        });
+
+        Ok(())
+    }
+
+    /// The council PDA must be set as the rewards pool's guardian.
+    pub fn council_pause_rewards(ctx: Context<CouncilPauseRewards>, pause: bool) -> Result<()> {
+        let council = &ctx.accounts.council;
+        let approvers = collect_council_approvals(council, ctx.remaining_accounts)?;
+        require!(
+            ctx.accounts.rewards_pool.emergency_pause != pause,
+            ErrorCode::PauseStateUnchanged
+        );
+
+        let seeds = &[b"council", council.governance.as_ref(), &[council.bump]];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = rewards_program::cpi::accounts::EmergencyPause {
+            rewards_pool: ctx.accounts.rewards_pool.to_account_info(),
+            authority: council.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.rewards_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        rewards_program::cpi::emergency_pause(cpi_ctx, pause)?;
+
+        let clock = Clock::get()?;
+        let council_action = &mut ctx.accounts.council_action;
+        council_action.council = council.key();
+        council_action.index = council.action_count;
+        council_action.kind = CouncilActionKind::RewardsPause;
+        council_action.target = ctx.accounts.rewards_pool.key();
+        council_action.pause = pause;
+        council_action.previous_pause = ctx.accounts.rewards_pool.emergency_pause;
+        council_action.approvers = approvers;
+        council_action.executed_at = clock.unix_timestamp;
+        council_action.expires_at = clock
+            .unix_timestamp
+            .checked_add(council.action_duration)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        council_action.status = CouncilActionStatus::Active;
+        council_action.bump = ctx.bumps.council_action;
+
+        let council = &mut ctx.accounts.council;
+        claim_council_target(council, council_action.target)?;
+        council.action_count += 1;
+
+        emit!(CouncilActionExecuted {
+            council: council.key(),
+            council_action: council_action.key(),
+            kind: council_action.kind,
+            target: council_action.target,
+            pause,
+            expires_at: council_action.expires_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn ratify_council_action(ctx: Context<RatifyCouncilAction>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        require!(
+            proposal.status == ProposalStatus::Passed,
+            ErrorCode::ProposalNotPassed
+        );
+        require!(!proposal.executed, ErrorCode::AlreadyExecuted);
+        require!(
+            proposal.proposal_type == ProposalType::CouncilRatification,
+            ErrorCode::InvalidProposalType
+        );
+
+        let council_action = &mut ctx.accounts.council_action;
+        require_keys_eq!(
+            proposal.council_action,
+            council_action.key(),
+            ErrorCode::CouncilActionMismatch
+        );
+        require!(
+            council_action.status == CouncilActionStatus::Active,
+            ErrorCode::CouncilActionNotActive
+        );
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp < council_action.expires_at,
+            ErrorCode::CouncilActionExpired
+        );
+
+        council_action.status = CouncilActionStatus::Ratified;
+        proposal.executed = true;
+        release_council_target(&mut ctx.accounts.council, council_action.target);
+
+        emit!(CouncilActionRatified {
+            council_action: council_action.key(),
+            proposal: proposal.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn expire_staking_action(ctx: Context<ExpireStakingAction>) -> Result<()> {
+        let council_action = &ctx.accounts.council_action;
+        require!(
+            council_action.kind == CouncilActionKind::StakingPause,
+            ErrorCode::CouncilActionMismatch
+        );
+        require!(
+            council_action.status == CouncilActionStatus::Active,
+            ErrorCode::CouncilActionNotActive
+        );
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= council_action.expires_at,
+            ErrorCode::CouncilActionNotExpired
+        );
+
+        // Only undo the action if nothing has moved the pool since; a later
+        // state change (e.g. an Emergency) takes precedence.
+        let restored = ctx.accounts.staking_pool.state == pool_state_for(council_action.pause);
+        if restored {
+            let council = &ctx.accounts.council;
+            let seeds = &[b"council", council.governance.as_ref(), &[council.bump]];
+            let signer = &[&seeds[..]];
+
+            let cpi_accounts = staking_program::cpi::accounts::SetPoolState {
+                staking_pool: ctx.accounts.staking_pool.to_account_info(),
+                authority: council.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.staking_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            staking_program::cpi::set_pool_state(
+                cpi_ctx,
+                pool_state_for(council_action.previous_pause),
+            )?;
+        }
+
+        let council_action = &mut ctx.accounts.council_action;
+        council_action.status = CouncilActionStatus::Expired;
+        release_council_target(&mut ctx.accounts.council, council_action.target);
+
+        emit!(CouncilActionExpired {
+            council_action: council_action.key(),
+            target: council_action.target,
+            pause: council_action.previous_pause,
+            restored,
+        });
+
+        Ok(())
+    }
+
+    pub fn expire_rewards_action(ctx: Context<ExpireRewardsAction>) -> Result<()> {
+        let council_action = &ctx.accounts.council_action;
+        require!(
+            council_action.kind == CouncilActionKind::RewardsPause,
+            ErrorCode::CouncilActionMismatch
+        );
+        require!(
+            council_action.status == CouncilActionStatus::Active,
+            ErrorCode::CouncilActionNotActive
+        );
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= council_action.expires_at,
+            ErrorCode::CouncilActionNotExpired
+        );
+
+        // Only undo the action if nothing has toggled the pool since.
+        let restored = ctx.accounts.rewards_pool.emergency_pause == council_action.pause;
+        if restored {
+            let council = &ctx.accounts.council;
+            let seeds = &[b"council", council.governance.as_ref(), &[council.bump]];
+            let signer = &[&seeds[..]];
+
+            let cpi_accounts = rewards_program::cpi::accounts::EmergencyPause {
+                rewards_pool: ctx.accounts.rewards_pool.to_account_info(),
+                authority: council.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.rewards_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            rewards_program::cpi::emergency_pause(cpi_ctx, council_action.previous_pause)?;
+        }
+
+        let council_action = &mut ctx.accounts.council_action;
+        council_action.status = CouncilActionStatus::Expired;
+        release_council_target(&mut ctx.accounts.council, council_action.target);
+
+        emit!(CouncilActionExpired {
+            council_action: council_action.key(),
+            target: council_action.target,
+            pause: council_action.previous_pause,
+            restored,
+        });
+
+        Ok(())
+    }
+
+    /// Rewrites a governance account created by the original program into
+    /// the current layout, growing it to fit the excluded accounts list. The
+    /// legacy quorum applies to every proposal type with standard voting.
+    pub fn migrate_legacy_governance(ctx: Context<MigrateLegacyGovernance>) -> Result<()> {
+        let governance_info = ctx.accounts.governance.to_account_info();
+        let legacy = {
+            let data = governance_info.try_borrow_data()?;
+            require!(
+                data.len() == 8 + LegacyGovernance::INIT_SPACE
+                    && data[..8] == Governance::DISCRIMINATOR,
+                ErrorCode::AlreadyMigrated
+            );
+            LegacyGovernance::deserialize(&mut &data[8..])?
+        };
+        require_keys_eq!(
+            legacy.authority,
+            ctx.accounts.authority.key(),
+            ErrorCode::Unauthorized
+        );
+
+        grow_account(
+            &governance_info,
+            8 + Governance::INIT_SPACE,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )?;
+
+        let governance = Governance {
+            authority: legacy.authority,
+            governance_token: legacy.governance_token,
+            voting_period: legacy.voting_period,
+            min_voting_power: legacy.min_voting_power,
+            proposal_rules: [ProposalRules {
+                voting_rule: VotingRule::Standard,
+                quorum_percentage: legacy.quorum_percentage,
+                veto_threshold_percentage: 0,
+            }; PROPOSAL_TYPE_COUNT],
+            voting_weight_curve: VotingWeightCurve::Linear,
+            proposal_count: legacy.proposal_count,
+            excluded_accounts: Vec::new(),
+            bump: legacy.bump,
+        };
+        governance.try_serialize(&mut &mut governance_info.try_borrow_mut_data()?[..])?;
+
+        emit!(LegacyGovernanceMigrated {
+            governance: governance_info.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Rewrites a proposal created by the original program into the current
+    /// layout so it can still be voted on, finalized and executed. Legacy
+    /// votes were counted linearly, so the raw tallies equal the weighted
+    /// ones. Permissionless; the payer covers the extra rent.
+    pub fn migrate_legacy_proposal(ctx: Context<MigrateLegacyProposal>) -> Result<()> {
+        let proposal_info = ctx.accounts.proposal.to_account_info();
+        let legacy = {
+            let data = proposal_info.try_borrow_data()?;
+            require!(
+                data.len() == 8 + LegacyProposal::INIT_SPACE
+                    && data[..8] == Proposal::DISCRIMINATOR,
+                ErrorCode::AlreadyMigrated
+            );
+            LegacyProposal::deserialize(&mut &data[8..])?
+        };
+
+        grow_account(
+            &proposal_info,
+            8 + Proposal::INIT_SPACE,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        let proposal = Proposal {
+            governance: legacy.governance,
+            proposer: legacy.proposer,
+            title: legacy.title,
+            description: legacy.description,
+            proposal_type: legacy.proposal_type,
+            start_time: legacy.start_time,
+            end_time: legacy.end_time,
+            yes_votes: legacy.yes_votes,
+            no_votes: legacy.no_votes,
+            raw_yes_votes: legacy.yes_votes,
+            raw_no_votes: legacy.no_votes,
+            voting_weight_curve: VotingWeightCurve::Linear,
+            status: legacy.status,
+            executed: legacy.executed,
+            council_action: Pubkey::default(),
+            bump: legacy.bump,
+        };
+        proposal.try_serialize(&mut &mut proposal_info.try_borrow_mut_data()?[..])?;
+
+        emit!(LegacyProposalMigrated {
+            proposal: proposal_info.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Rewrites a vote record created by the original program into the
+    /// current layout; its effective weight is its linear voting power.
+    /// Permissionless; the payer covers the extra rent.
+    pub fn migrate_legacy_vote_record(ctx: Context<MigrateLegacyVoteRecord>) -> Result<()> {
+        let vote_record_info = ctx.accounts.vote_record.to_account_info();
+        let legacy = {
+            let data = vote_record_info.try_borrow_data()?;
+            require!(
+                data.len() == 8 + LegacyVoteRecord::INIT_SPACE
+                    && data[..8] == VoteRecord::DISCRIMINATOR,
+                ErrorCode::AlreadyMigrated
+            );
+            LegacyVoteRecord::deserialize(&mut &data[8..])?
+        };
+
+        grow_account(
+            &vote_record_info,
+            8 + VoteRecord::INIT_SPACE,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+
+        let vote_record = VoteRecord {
+            proposal: legacy.proposal,
+            voter: legacy.voter,
+            vote: legacy.vote,
+            voting_power: legacy.voting_power,
+            effective_weight: legacy.voting_power,
+            timestamp: legacy.timestamp,
+            bump: legacy.bump,
+        };
+        vote_record.try_serialize(&mut &mut vote_record_info.try_borrow_mut_data()?[..])?;
+
+        emit!(LegacyVoteRecordMigrated {
+            vote_record: vote_record_info.key(),
+        });
+
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
@@ -192,37 +911,146 @@ pub struct InitializeGovernance<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(title: String)]
-pub struct CreateProposal<'info> {
+pub struct SetExcludedAccounts<'info> {
     #[account(
+        mut,
         seeds = [b"governance", governance.governance_token.as_ref()],
-        bump = governance.bump
+        bump = governance.bump,
+        has_one = authority
     )]
     pub governance: Account<'info, Governance>,
 
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetProposalRules<'info> {
     #[account(
-        init,
-        payer = proposer,
-        space = 8 + Proposal::INIT_SPACE,
-        seeds = [b"proposal", governance.key().as_ref(), &governance.proposal_count.to_le_bytes()],
-        bump
+        mut,
+        seeds = [b"governance", governance.governance_token.as_ref()],
+        bump = governance.bump,
+        has_one = authority
     )]
-    pub proposal: Account<'info, Proposal>,
-
-    #[account(mut)]
-    pub proposer: Signer<'info>,
-
-    pub voter_account: Account<'info, TokenAccount>,
+    pub governance: Account<'info, Governance>,
 
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct CastVote<'info> {
+pub struct SetVotingWeightCurve<'info> {
     #[account(
+        mut,
         seeds = [b"governance", governance.governance_token.as_ref()],
-        bump = governance.bump
+        bump = governance.bump,
+        has_one = authority
+    )]
+    pub governance: Account<'info, Governance>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LockVotingTokens<'info> {
+    #[account(
+        seeds = [b"governance", governance.governance_token.as_ref()],
+        bump = governance.bump
+    )]
+    pub governance: Account<'info, Governance>,
+
+    #[account(address = governance.governance_token)]
+    pub governance_token: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + VoteLock::INIT_SPACE,
+        seeds = [b"vote_lock", governance.key().as_ref(), voter.key().as_ref()],
+        bump
+    )]
+    pub vote_lock: Account<'info, VoteLock>,
+
+    #[account(
+        init,
+        payer = voter,
+        seeds = [b"vote_lock_vault", vote_lock.key().as_ref()],
+        bump,
+        token::mint = governance_token,
+        token::authority = vote_lock,
+    )]
+    pub lock_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub voter: Signer<'info>,
+
+    #[account(mut)]
+    pub voter_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UnlockVotingTokens<'info> {
+    #[account(
+        mut,
+        seeds = [b"vote_lock", vote_lock.governance.as_ref(), voter.key().as_ref()],
+        bump = vote_lock.bump,
+        has_one = voter,
+        close = voter
+    )]
+    pub vote_lock: Account<'info, VoteLock>,
+
+    #[account(
+        mut,
+        seeds = [b"vote_lock_vault", vote_lock.key().as_ref()],
+        bump
+    )]
+    pub lock_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub voter: Signer<'info>,
+
+    #[account(mut)]
+    pub voter_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(title: String)]
+pub struct CreateProposal<'info> {
+    #[account(
+        seeds = [b"governance", governance.governance_token.as_ref()],
+        bump = governance.bump
+    )]
+    pub governance: Account<'info, Governance>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::INIT_SPACE,
+        seeds = [b"proposal", governance.key().as_ref(), &governance.proposal_count.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    pub voter_account: Account<'info, TokenAccount>,
+
+    pub council_action: Option<Account<'info, CouncilAction>>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CastVote<'info> {
+    #[account(
+        seeds = [b"governance", governance.governance_token.as_ref()],
+        bump = governance.bump
     )]
     pub governance: Account<'info, Governance>,
 
@@ -245,8 +1073,18 @@ pub struct CastVote<'info> {
     #[account(mut)]
     pub voter: Signer<'info>,
 
+    #[account(
+        constraint = voter_account.owner == voter.key(),
+        constraint = voter_account.mint == governance.governance_token
+    )]
     pub voter_account: Account<'info, TokenAccount>,
 
+    #[account(
+        seeds = [b"vote_lock", governance.key().as_ref(), voter.key().as_ref()],
+        bump = vote_lock.bump
+    )]
+    pub vote_lock: Option<Account<'info, VoteLock>>,
+
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
@@ -259,6 +1097,7 @@ pub struct FinalizeProposal<'info> {
     )]
     pub governance: Account<'info, Governance>,
 
+    #[account(address = governance.governance_token)]
     pub governance_token: Account<'info, Mint>,
 
     #[account(
@@ -290,9 +1129,245 @@ pub struct ExecuteProposal<'info> {
     pub executor: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct InitializeCouncil<'info> {
+    #[account(
+        seeds = [b"governance", governance.governance_token.as_ref()],
+        bump = governance.bump,
+        has_one = authority
+    )]
+    pub governance: Account<'info, Governance>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Council::INIT_SPACE,
+        seeds = [b"council", governance.key().as_ref()],
+        bump
+    )]
+    pub council: Account<'info, Council>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCouncil<'info> {
+    #[account(
+        seeds = [b"governance", governance.governance_token.as_ref()],
+        bump = governance.bump,
+        has_one = authority
+    )]
+    pub governance: Account<'info, Governance>,
+
+    #[account(
+        mut,
+        seeds = [b"council", governance.key().as_ref()],
+        bump = council.bump,
+        has_one = governance
+    )]
+    pub council: Account<'info, Council>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CouncilPauseStaking<'info> {
+    #[account(
+        mut,
+        seeds = [b"council", council.governance.as_ref()],
+        bump = council.bump
+    )]
+    pub council: Account<'info, Council>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CouncilAction::INIT_SPACE,
+        seeds = [b"council_action", council.key().as_ref(), &council.action_count.to_le_bytes()],
+        bump
+    )]
+    pub council_action: Account<'info, CouncilAction>,
+
+    #[account(mut)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    pub staking_program: Program<'info, StakingProgram>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CouncilPauseRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"council", council.governance.as_ref()],
+        bump = council.bump
+    )]
+    pub council: Account<'info, Council>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CouncilAction::INIT_SPACE,
+        seeds = [b"council_action", council.key().as_ref(), &council.action_count.to_le_bytes()],
+        bump
+    )]
+    pub council_action: Account<'info, CouncilAction>,
+
+    #[account(mut)]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    pub rewards_program: Program<'info, RewardsProgram>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RatifyCouncilAction<'info> {
+    #[account(
+        seeds = [b"governance", governance.governance_token.as_ref()],
+        bump = governance.bump
+    )]
+    pub governance: Account<'info, Governance>,
+
+    #[account(
+        mut,
+        seeds = [b"council", governance.key().as_ref()],
+        bump = council.bump,
+        has_one = governance
+    )]
+    pub council: Account<'info, Council>,
+
+    #[account(
+        mut,
+        seeds = [b"council_action", council.key().as_ref(), &council_action.index.to_le_bytes()],
+        bump = council_action.bump,
+        has_one = council
+    )]
+    pub council_action: Account<'info, CouncilAction>,
+
+    #[account(mut, has_one = governance)]
+    pub proposal: Account<'info, Proposal>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireStakingAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"council", council.governance.as_ref()],
+        bump = council.bump
+    )]
+    pub council: Account<'info, Council>,
+
+    #[account(
+        mut,
+        seeds = [b"council_action", council.key().as_ref(), &council_action.index.to_le_bytes()],
+        bump = council_action.bump,
+        has_one = council
+    )]
+    pub council_action: Account<'info, CouncilAction>,
+
+    #[account(mut, address = council_action.target)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    pub staking_program: Program<'info, StakingProgram>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireRewardsAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"council", council.governance.as_ref()],
+        bump = council.bump
+    )]
+    pub council: Account<'info, Council>,
+
+    #[account(
+        mut,
+        seeds = [b"council_action", council.key().as_ref(), &council_action.index.to_le_bytes()],
+        bump = council_action.bump,
+        has_one = council
+    )]
+    pub council_action: Account<'info, CouncilAction>,
+
+    #[account(mut, address = council_action.target)]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    pub rewards_program: Program<'info, RewardsProgram>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyGovernance<'info> {
+    /// CHECK: Still in the legacy layout; validated and rewritten by the
+    /// instruction.
+    #[account(
+        mut,
+        seeds = [b"governance", governance_token.key().as_ref()],
+        bump
+    )]
+    pub governance: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub governance_token: Account<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyProposal<'info> {
+    /// CHECK: Still in the legacy layout; the owner, discriminator and legacy
+    /// size are validated by the instruction before it is rewritten.
+    #[account(mut, owner = crate::ID)]
+    pub proposal: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyVoteRecord<'info> {
+    /// CHECK: Still in the legacy layout; the owner, discriminator and legacy
+    /// size are validated by the instruction before it is rewritten.
+    #[account(mut, owner = crate::ID)]
+    pub vote_record: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[account]
 #[derive(InitSpace)]
 pub struct Governance {
+    pub authority: Pubkey,
+    pub governance_token: Pubkey,
+    pub voting_period: i64,
+    pub min_voting_power: u64,
+    pub proposal_rules: [ProposalRules; PROPOSAL_TYPE_COUNT],
+    pub voting_weight_curve: VotingWeightCurve,
+    pub proposal_count: u64,
+    #[max_len(MAX_EXCLUDED_ACCOUNTS)]
+    pub excluded_accounts: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+/// `Governance` as written by the original program.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct LegacyGovernance {
     pub authority: Pubkey,
     pub governance_token: Pubkey,
     pub voting_period: i64,
@@ -305,6 +1380,29 @@ pub struct Governance {
 #[account]
 #[derive(InitSpace)]
 pub struct Proposal {
+    pub governance: Pubkey,
+    pub proposer: Pubkey,
+    #[max_len(128)]
+    pub title: String,
+    #[max_len(512)]
+    pub description: String,
+    pub proposal_type: ProposalType,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub yes_votes: u64,
+    pub no_votes: u64,
+    pub raw_yes_votes: u64,
+    pub raw_no_votes: u64,
+    pub voting_weight_curve: VotingWeightCurve,
+    pub status: ProposalStatus,
+    pub executed: bool,
+    pub council_action: Pubkey,
+    pub bump: u8,
+}
+
+/// `Proposal` as written by the original program.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct LegacyProposal {
     pub governance: Pubkey,
     pub proposer: Pubkey,
     #[max_len(128)]
@@ -324,6 +1422,18 @@ pub struct Proposal {
 #[account]
 #[derive(InitSpace)]
 pub struct VoteRecord {
+    pub proposal: Pubkey,
+    pub voter: Pubkey,
+    pub vote: Vote,
+    pub voting_power: u64,
+    pub effective_weight: u64,
+    pub timestamp: i64,
+    pub bump: u8,
+}
+
+/// `VoteRecord` as written by the original program.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct LegacyVoteRecord {
     pub proposal: Pubkey,
     pub voter: Pubkey,
     pub vote: Vote,
@@ -332,12 +1442,88 @@ pub struct VoteRecord {
     pub bump: u8,
 }
 
+#[account]
+#[derive(InitSpace)]
+pub struct VoteLock {
+    pub governance: Pubkey,
+    pub voter: Pubkey,
+    pub amount: u64,
+    pub lock_start: i64,
+    pub lock_end: i64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Council {
+    pub governance: Pubkey,
+    #[max_len(MAX_COUNCIL_MEMBERS)]
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
+    pub action_duration: i64,
+    pub action_count: u64,
+    /// Targets with an active action; each target has at most one, so
+    /// overlapping actions cannot undo each other on expiry.
+    #[max_len(MAX_ACTIVE_COUNCIL_ACTIONS)]
+    pub active_targets: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CouncilAction {
+    pub council: Pubkey,
+    pub index: u64,
+    pub kind: CouncilActionKind,
+    pub target: Pubkey,
+    pub pause: bool,
+    /// Whether the target was paused before the action, restored on expiry.
+    pub previous_pause: bool,
+    #[max_len(MAX_COUNCIL_MEMBERS)]
+    pub approvers: Vec<Pubkey>,
+    pub executed_at: i64,
+    pub expires_at: i64,
+    pub status: CouncilActionStatus,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub struct ProposalRules {
+    pub voting_rule: VotingRule,
+    pub quorum_percentage: u64,
+    pub veto_threshold_percentage: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+pub enum VotingRule {
+    /// Passes with quorum and more yes than no votes.
+    #[default]
+    Standard,
+    /// Passes after the voting period unless no votes reach the veto threshold.
+    Optimistic,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum VotingWeightCurve {
+    /// One token, one vote.
+    Linear,
+    /// Weight is the integer square root of the token amount.
+    Quadratic,
+    /// Locked tokens gain up to `max_bonus_bps` extra weight, scaling with
+    /// the lock duration up to `max_lock_duration` seconds.
+    Conviction {
+        max_lock_duration: i64,
+        max_bonus_bps: u64,
+    },
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
 pub enum ProposalType {
     ParameterChange,
     TreasurySpend,
     ProtocolUpgrade,
     General,
+    CouncilRatification,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
@@ -347,12 +1533,40 @@ pub enum ProposalStatus {
     Rejected,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum CouncilActionKind {
+    StakingPause,
+    RewardsPause,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum CouncilActionStatus {
+    Active,
+    Ratified,
+    Expired,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
 pub enum Vote {
     Yes,
     No,
 }
 
+#[event]
+pub struct LegacyGovernanceMigrated {
+    pub governance: Pubkey,
+}
+
+#[event]
+pub struct LegacyProposalMigrated {
+    pub proposal: Pubkey,
+}
+
+#[event]
+pub struct LegacyVoteRecordMigrated {
+    pub vote_record: Pubkey,
+}
+
 #[event]
 pub struct GovernanceInitialized {
     pub governance: Pubkey,
@@ -360,6 +1574,21 @@ pub struct GovernanceInitialized {
     pub voting_period: i64,
 }
 
+#[event]
+pub struct ExcludedAccountsUpdated {
+    pub governance: Pubkey,
+    pub excluded_accounts: Vec<Pubkey>,
+}
+
+#[event]
+pub struct ProposalRulesUpdated {
+    pub governance: Pubkey,
+    pub proposal_type: ProposalType,
+    pub voting_rule: VotingRule,
+    pub quorum_percentage: u64,
+    pub veto_threshold_percentage: u64,
+}
+
 #[event]
 pub struct ProposalCreated {
     pub proposal: Pubkey,
@@ -374,6 +1603,26 @@ pub struct VoteCast {
     pub voter: Pubkey,
     pub vote: Vote,
     pub voting_power: u64,
+    pub effective_weight: u64,
+}
+
+#[event]
+pub struct VotingWeightCurveUpdated {
+    pub governance: Pubkey,
+    pub voting_weight_curve: VotingWeightCurve,
+}
+
+#[event]
+pub struct VotingTokensLocked {
+    pub voter: Pubkey,
+    pub amount: u64,
+    pub lock_end: i64,
+}
+
+#[event]
+pub struct VotingTokensUnlocked {
+    pub voter: Pubkey,
+    pub amount: u64,
 }
 
 #[event]
@@ -382,6 +1631,8 @@ pub struct ProposalFinalized {
     pub status: ProposalStatus,
     pub yes_votes: u64,
     pub no_votes: u64,
+    pub circulating_supply: u64,
+    pub voting_rule: VotingRule,
 }
 
 #[event]
@@ -390,6 +1641,38 @@ pub struct ProposalExecuted {
     pub executor: Pubkey,
 }
 
+#[event]
+pub struct CouncilUpdated {
+    pub council: Pubkey,
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
+    pub action_duration: i64,
+}
+
+#[event]
+pub struct CouncilActionExecuted {
+    pub council: Pubkey,
+    pub council_action: Pubkey,
+    pub kind: CouncilActionKind,
+    pub target: Pubkey,
+    pub pause: bool,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct CouncilActionRatified {
+    pub council_action: Pubkey,
+    pub proposal: Pubkey,
+}
+
+#[event]
+pub struct CouncilActionExpired {
+    pub council_action: Pubkey,
+    pub target: Pubkey,
+    pub pause: bool,
+    pub restored: bool,
+}
+
 #[error_code]
 pub enum ErrorCode {
     #[msg("Insufficient voting power")]
@@ -404,4 +1687,227 @@ pub enum ErrorCode {
     ProposalNotPassed,
     #[msg("Proposal already executed")]
     AlreadyExecuted,
+    #[msg("Quorum percentage must not exceed 100")]
+    InvalidQuorumPercentage,
+    #[msg("Veto threshold must be between 1 and 100")]
+    InvalidVetoThreshold,
+    #[msg("Optimistic voting is only allowed for parameter changes")]
+    OptimisticNotAllowed,
+    #[msg("Too many excluded accounts")]
+    TooManyExcludedAccounts,
+    #[msg("Duplicate excluded account")]
+    DuplicateExcludedAccount,
+    #[msg("Excluded accounts do not match the registered set")]
+    ExcludedAccountsMismatch,
+    #[msg("Excluded account does not hold the governance token")]
+    InvalidExcludedAccount,
+    #[msg("Arithmetic overflow in calculation")]
+    ArithmeticOverflow,
+    #[msg("Invalid council configuration")]
+    InvalidCouncil,
+    #[msg("Council action duration must exceed the voting period")]
+    InvalidActionDuration,
+    #[msg("Signer is not a council member")]
+    NotCouncilMember,
+    #[msg("Council member account did not sign")]
+    MissingCouncilSignature,
+    #[msg("Council member approved more than once")]
+    DuplicateCouncilApproval,
+    #[msg("Not enough council approvals")]
+    InsufficientCouncilApprovals,
+    #[msg("Council ratification proposals require a council action")]
+    MissingCouncilAction,
+    #[msg("Council action is not active")]
+    CouncilActionNotActive,
+    #[msg("Council action does not match")]
+    CouncilActionMismatch,
+    #[msg("Council action has expired")]
+    CouncilActionExpired,
+    #[msg("Council action has not expired")]
+    CouncilActionNotExpired,
+    #[msg("Council can only pause or unpause an active or paused pool")]
+    PoolStateNotTogglable,
+    #[msg("Pool is already in the requested pause state")]
+    PauseStateUnchanged,
+    #[msg("Target already has an active council action")]
+    CouncilTargetBusy,
+    #[msg("Too many active council actions")]
+    TooManyActiveCouncilActions,
+    #[msg("Invalid proposal type")]
+    InvalidProposalType,
+    #[msg("Lock amount must be greater than zero")]
+    InvalidLockAmount,
+    #[msg("Lock duration must be greater than zero")]
+    InvalidLockDuration,
+    #[msg("Vote lock has not expired")]
+    VoteLockNotExpired,
+    #[msg("Conviction voting requires a vote lock")]
+    MissingVoteLock,
+    #[msg("Vote lock ends before the voting period")]
+    VoteLockTooShort,
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+    #[msg("Account is not in the legacy layout")]
+    AlreadyMigrated,
+}
+
+/// Reallocs a program-owned account to `space`, topping its rent up from
+/// `payer`.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    space: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let rent_due = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if rent_due > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        let cpi_ctx = CpiContext::new(system_program.to_account_info(), cpi_accounts);
+
+        system_program::transfer(cpi_ctx, rent_due)?;
+    }
+
+    account.realloc(space, true)?;
+
+    Ok(())
+}
+
+/// Sums the balances of the registered excluded token accounts, which must be
+/// passed as remaining accounts in the same order they were registered.
+fn excluded_supply(governance: &Governance, remaining_accounts: &[AccountInfo]) -> Result<u64> {
+    require!(
+        remaining_accounts.len() == governance.excluded_accounts.len(),
+        ErrorCode::ExcludedAccountsMismatch
+    );
+
+    let mut excluded: u64 = 0;
+    for (info, expected) in remaining_accounts.iter().zip(governance.excluded_accounts.iter()) {
+        require_keys_eq!(info.key(), *expected, ErrorCode::ExcludedAccountsMismatch);
+
+        require_keys_eq!(*info.owner, token::ID, ErrorCode::InvalidExcludedAccount);
+        let token_account = TokenAccount::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        require_keys_eq!(
+            token_account.mint,
+            governance.governance_token,
+            ErrorCode::InvalidExcludedAccount
+        );
+
+        excluded = excluded
+            .checked_add(token_account.amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+    }
+
+    Ok(excluded)
+}
+
+fn validate_council(members: &[Pubkey], threshold: u8) -> Result<()> {
+    require!(
+        !members.is_empty() && members.len() <= MAX_COUNCIL_MEMBERS,
+        ErrorCode::InvalidCouncil
+    );
+    require!(
+        threshold > 0 && threshold as usize <= members.len(),
+        ErrorCode::InvalidCouncil
+    );
+    for (i, member) in members.iter().enumerate() {
+        require!(!members[..i].contains(member), ErrorCode::InvalidCouncil);
+    }
+
+    Ok(())
+}
+
+/// Collects the council members that signed the transaction, passed as
+/// remaining accounts, and checks that they reach the council threshold.
+fn collect_council_approvals(
+    council: &Council,
+    remaining_accounts: &[AccountInfo],
+) -> Result<Vec<Pubkey>> {
+    let mut approvers: Vec<Pubkey> = Vec::new();
+    for info in remaining_accounts {
+        require!(info.is_signer, ErrorCode::MissingCouncilSignature);
+        require!(council.members.contains(info.key), ErrorCode::NotCouncilMember);
+        require!(!approvers.contains(info.key), ErrorCode::DuplicateCouncilApproval);
+        approvers.push(info.key());
+    }
+
+    require!(
+        approvers.len() >= council.threshold as usize,
+        ErrorCode::InsufficientCouncilApprovals
+    );
+
+    Ok(approvers)
+}
+
+fn effective_weight(
+    curve: VotingWeightCurve,
+    voting_power: u64,
+    committed_duration: i64,
+) -> Result<u64> {
+    let weight = match curve {
+        VotingWeightCurve::Linear => voting_power as u128,
+        VotingWeightCurve::Quadratic => integer_sqrt(voting_power as u128),
+        VotingWeightCurve::Conviction {
+            max_lock_duration,
+            max_bonus_bps,
+        } => {
+            let duration = committed_duration.clamp(0, max_lock_duration) as u128;
+            let bonus_bps = (max_bonus_bps as u128)
+                .checked_mul(duration)
+                .ok_or(ErrorCode::ArithmeticOverflow)?
+                / max_lock_duration as u128;
+
+            (voting_power as u128)
+                .checked_mul(10000 + bonus_bps)
+                .ok_or(ErrorCode::ArithmeticOverflow)?
+                / 10000
+        }
+    };
+
+    u64::try_from(weight).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+
+    let mut x = value;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+/// Marks `target` as having an active council action.
+fn claim_council_target(council: &mut Council, target: Pubkey) -> Result<()> {
+    require!(
+        !council.active_targets.contains(&target),
+        ErrorCode::CouncilTargetBusy
+    );
+    require!(
+        council.active_targets.len() < MAX_ACTIVE_COUNCIL_ACTIONS,
+        ErrorCode::TooManyActiveCouncilActions
+    );
+    council.active_targets.push(target);
+
+    Ok(())
+}
+
+fn release_council_target(council: &mut Council, target: Pubkey) {
+    council.active_targets.retain(|active| *active != target);
+}
+
+fn pool_state_for(pause: bool) -> PoolState {
+    if pause {
+        PoolState::Paused
+    } else {
+        PoolState::Active
+    }
 }
@@ -1,8 +1,19 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_lang::{system_program, Discriminator};
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount, Transfer};
+use staking_program::StakingPool;
 
 declare_id!("rewrDSBhqKMxLkhJRnEKRsJKt7rPt5bBF4VVXsEK6Nx");
 
+const REWARD_PRECISION: u128 = 1_000_000_000_000;
+const SECONDS_PER_YEAR: u64 = 31_536_000;
+const LEGACY_SLOT_MS: u64 = 400;
+const MAX_APY_CURVE_POINTS: usize = 8;
+const MAX_COMPOUND_FEE_BPS: u16 = 500;
+const MAX_COMPOUND_BATCH: usize = 16;
+const MAX_REWARD_STREAMS: usize = 4;
+const STREAM_CLAIM_WINDOW: i64 = 30 * 86_400;
+
 #[program]
 pub mod rewards_program {
     use super::*;
@@ -15,19 +26,39 @@ pub mod rewards_program {
         compound_frequency: u64,
         emergency_pause: bool,
     ) -> Result<()> {
+        require!(
+            min_apy <= base_apy && base_apy <= max_apy,
+            ErrorCode::InvalidAPY
+        );
+
         let rewards_pool = &mut ctx.accounts.rewards_pool;
         rewards_pool.authority = ctx.accounts.authority.key();
+        rewards_pool.guardian = Pubkey::default();
         rewards_pool.reward_mint = ctx.accounts.reward_mint.key();
         rewards_pool.reward_vault = ctx.accounts.reward_vault.key();
+        rewards_pool.stake_mint = ctx.accounts.stake_mint.key();
+        rewards_pool.stake_vault = ctx.accounts.stake_vault.key();
+        rewards_pool.stake_decimals = ctx.accounts.stake_mint.decimals;
         rewards_pool.base_apy = base_apy;
         rewards_pool.current_apy = base_apy;
         rewards_pool.min_apy = min_apy;
         rewards_pool.max_apy = max_apy;
         rewards_pool.compound_frequency = compound_frequency;
+        rewards_pool.compound_fee_bps = 0;
+        rewards_pool.stream_ids = [0; MAX_REWARD_STREAMS];
+        rewards_pool.next_stream_id = 1;
         rewards_pool.total_staked = 0;
         rewards_pool.total_rewards_distributed = 0;
+        rewards_pool.reward_balance = 0;
+        rewards_pool.total_liabilities = 0;
+        rewards_pool.reward_per_token_stored = 0;
+        rewards_pool.haircut_enabled = false;
+        rewards_pool.staking_pool = Pubkey::default();
+        rewards_pool.min_apy_update_interval = 0;
+        store_apy_curve(rewards_pool, &default_apy_curve(min_apy, max_apy))?;
         rewards_pool.emergency_pause = emergency_pause;
-        rewards_pool.last_update_slot = Clock::get()?.slot;
+        rewards_pool.last_update_time = Clock::get()?.unix_timestamp;
+        rewards_pool.last_apy_update_time = rewards_pool.last_update_time;
         rewards_pool.bump = ctx.bumps.rewards_pool;
 
         emit!(RewardsPoolInitialized {
@@ -39,23 +70,77 @@ pub mod rewards_program {
         Ok(())
     }
 
-    pub fn update_apy_dynamic(ctx: Context<UpdateAPY>, tvl: u64) -> Result<()> {
+    /// Links a staking-program pool whose TVL feeds the APY curve
+    /// (`Pubkey::default()` to unlink) and sets the minimum number of seconds
+    /// between permissionless APY updates.
+    pub fn configure_apy_updates(
+        ctx: Context<ConfigureAPYUpdates>,
+        staking_pool: Pubkey,
+        min_apy_update_interval: u64,
+    ) -> Result<()> {
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        rewards_pool.staking_pool = staking_pool;
+        rewards_pool.min_apy_update_interval = min_apy_update_interval;
+
+        emit!(APYUpdateConfigUpdated {
+            pool: rewards_pool.key(),
+            staking_pool,
+            min_apy_update_interval,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_apy_curve(ctx: Context<SetAPYCurve>, curve: ApyCurve) -> Result<()> {
         let rewards_pool = &mut ctx.accounts.rewards_pool;
+        store_apy_curve(rewards_pool, &curve)?;
+
+        emit!(APYCurveUpdated {
+            pool: rewards_pool.key(),
+            model: rewards_pool.apy_curve_model,
+            points: rewards_pool.apy_curve[..rewards_pool.apy_curve_len as usize].to_vec(),
+        });
+
+        Ok(())
+    }
+
+    pub fn update_apy_dynamic(ctx: Context<UpdateAPY>) -> Result<()> {
+        let rewards_pool = &ctx.accounts.rewards_pool;
         require!(!rewards_pool.emergency_pause, ErrorCode::PoolPaused);
 
-        let new_apy = calculate_dynamic_apy(
-            rewards_pool.base_apy,
-            rewards_pool.min_apy,
-            rewards_pool.max_apy,
-            tvl,
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp
+                >= rewards_pool.last_apy_update_time + rewards_pool.min_apy_update_interval as i64,
+            ErrorCode::APYUpdateTooSoon
         );
 
+        let mut tvl = rewards_pool.total_staked;
+        if rewards_pool.staking_pool != Pubkey::default() {
+            let staking_pool = ctx
+                .accounts
+                .staking_pool
+                .as_ref()
+                .ok_or(ErrorCode::InvalidStakingPool)?;
+            require_keys_eq!(
+                staking_pool.key(),
+                rewards_pool.staking_pool,
+                ErrorCode::InvalidStakingPool
+            );
+            tvl = tvl.saturating_add(staking_pool.total_staked);
+        }
+
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        let old_apy = rewards_pool.current_apy;
+        let new_apy = calculate_dynamic_apy(rewards_pool, tvl);
+
+        update_reward_per_token(rewards_pool, clock.unix_timestamp)?;
         rewards_pool.current_apy = new_apy;
-        rewards_pool.last_update_slot = Clock::get()?.slot;
+        rewards_pool.last_apy_update_time = clock.unix_timestamp;
 
         emit!(APYUpdated {
             pool: rewards_pool.key(),
-            old_apy: rewards_pool.current_apy,
+            old_apy,
             new_apy,
             tvl,
         });
@@ -63,21 +148,163 @@ pub mod rewards_program {
         Ok(())
     }
 
-    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
-        let rewards_pool = &ctx.accounts.rewards_pool;
+    pub fn initialize_user_stake(ctx: Context<InitializeUserStake>) -> Result<()> {
+        let user_stake = &mut ctx.accounts.user_stake;
+        user_stake.user = ctx.accounts.user.key();
+        user_stake.pool = ctx.accounts.rewards_pool.key();
+        user_stake.staked_amount = 0;
+        user_stake.total_rewards_claimed = 0;
+        user_stake.unpaid_rewards = 0;
+        user_stake.reward_per_token_paid = ctx.accounts.rewards_pool.reward_per_token_stored;
+        user_stake.auto_compound = false;
+        user_stake.last_compound_time = 0;
+        user_stake.stream_checkpoints = [StreamCheckpoint::default(); MAX_REWARD_STREAMS];
+        user_stake.bump = ctx.bumps.user_stake;
+
+        Ok(())
+    }
+
+    /// Remaining accounts: every reward stream of the pool, in index order.
+    pub fn deposit<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deposit<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
         require!(!rewards_pool.emergency_pause, ErrorCode::PoolPaused);
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let clock = Clock::get()?;
+        update_reward_per_token(rewards_pool, clock.unix_timestamp)?;
+        settle_user_rewards(rewards_pool, &mut ctx.accounts.user_stake);
+
+        let reward_streams =
+            load_reward_streams(rewards_pool, ctx.remaining_accounts, clock.unix_timestamp)?;
+        settle_stream_rewards(&reward_streams, &mut ctx.accounts.user_stake);
+        exit_reward_streams(&reward_streams)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.stake_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, amount)?;
 
         let user_stake = &mut ctx.accounts.user_stake;
+        user_stake.staked_amount += amount;
+
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        rewards_pool.total_staked += amount;
+
+        emit!(Deposited {
+            user: ctx.accounts.user.key(),
+            amount,
+            staked_amount: user_stake.staked_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Remaining accounts: every reward stream of the pool, in index order.
+    pub fn withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        require!(
+            amount <= ctx.accounts.user_stake.staked_amount,
+            ErrorCode::InsufficientStake
+        );
+
         let clock = Clock::get()?;
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        update_reward_per_token(rewards_pool, clock.unix_timestamp)?;
+        settle_user_rewards(rewards_pool, &mut ctx.accounts.user_stake);
+
+        let reward_streams =
+            load_reward_streams(rewards_pool, ctx.remaining_accounts, clock.unix_timestamp)?;
+        settle_stream_rewards(&reward_streams, &mut ctx.accounts.user_stake);
+        exit_reward_streams(&reward_streams)?;
+
+        let rewards_pool = &ctx.accounts.rewards_pool;
+        let seeds = &[
+            b"rewards_pool",
+            rewards_pool.reward_mint.as_ref(),
+            &[rewards_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
 
-        let pending_rewards = calculate_rewards(
-            user_stake.staked_amount,
-            rewards_pool.current_apy,
-            user_stake.last_claim_slot,
-            clock.slot,
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.stake_vault.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: rewards_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let user_stake = &mut ctx.accounts.user_stake;
+        user_stake.staked_amount -= amount;
+
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        rewards_pool.total_staked -= amount;
+
+        emit!(Withdrawn {
+            user: ctx.accounts.user.key(),
+            amount,
+            staked_amount: user_stake.staked_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn close_user_stake(ctx: Context<CloseUserStake>) -> Result<()> {
+        let user_stake = &ctx.accounts.user_stake;
+        require!(
+            user_stake.staked_amount == 0 && user_stake.unpaid_rewards == 0,
+            ErrorCode::StakeNotEmpty
+        );
+        require!(
+            user_stake
+                .stream_checkpoints
+                .iter()
+                .zip(ctx.accounts.rewards_pool.stream_ids.iter())
+                .all(|(checkpoint, stream_id)| {
+                    checkpoint.unpaid_rewards == 0 || checkpoint.stream_id != *stream_id
+                }),
+            ErrorCode::StakeNotEmpty
         );
 
-        require!(pending_rewards > 0, ErrorCode::NoRewardsToClaim);
+        Ok(())
+    }
+
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        require!(!rewards_pool.emergency_pause, ErrorCode::PoolPaused);
+
+        let user_stake = &mut ctx.accounts.user_stake;
+        let clock = Clock::get()?;
+
+        update_reward_per_token(rewards_pool, clock.unix_timestamp)?;
+        settle_user_rewards(rewards_pool, user_stake);
+
+        let owed_rewards = user_stake.unpaid_rewards;
+        require!(owed_rewards > 0, ErrorCode::NoRewardsToClaim);
+
+        let total_liabilities = rewards_pool.total_liabilities;
+        let payout = payable_rewards(rewards_pool, total_liabilities, owed_rewards);
+        require!(payout > 0, ErrorCode::InsufficientRewardFunds);
+
+        // In haircut mode the unpaid share is forfeited; otherwise it stays owed
+        // until the pool is topped up.
+        let unpaid_rewards = if rewards_pool.haircut_enabled {
+            0
+        } else {
+            owed_rewards - payout
+        };
 
         let seeds = &[
             b"rewards_pool",
@@ -94,87 +321,1029 @@ pub mod rewards_program {
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
 
-        token::transfer(cpi_ctx, pending_rewards)?;
+        token::transfer(cpi_ctx, payout)?;
 
-        user_stake.total_rewards_claimed += pending_rewards;
-        user_stake.last_claim_slot = clock.slot;
+        user_stake.total_rewards_claimed += payout;
+        user_stake.unpaid_rewards = unpaid_rewards;
 
         let rewards_pool = &mut ctx.accounts.rewards_pool;
-        rewards_pool.total_rewards_distributed += pending_rewards;
+        rewards_pool.total_liabilities = rewards_pool
+            .total_liabilities
+            .saturating_sub(owed_rewards - unpaid_rewards);
+        rewards_pool.reward_balance -= payout;
+        rewards_pool.total_rewards_distributed += payout;
 
         emit!(RewardsClaimed {
             user: ctx.accounts.user.key(),
-            amount: pending_rewards,
-            slot: clock.slot,
+            amount: payout,
+            forfeited: owed_rewards - payout - unpaid_rewards,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    /// Moves a user's rewards from the reward vault into their stake, at most
+    /// once per `compound_frequency` seconds. Remaining accounts: every reward
+    /// stream of the pool, in index order.
+    pub fn compound_rewards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CompoundRewards<'info>>,
+    ) -> Result<()> {
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        require!(!rewards_pool.emergency_pause, ErrorCode::PoolPaused);
+        require!(
+            rewards_pool.reward_mint == rewards_pool.stake_mint,
+            ErrorCode::CompoundingUnsupported
+        );
+
+        let user_stake = &mut ctx.accounts.user_stake;
+        let clock = Clock::get()?;
+        require!(
+            compound_due(rewards_pool, user_stake, clock.unix_timestamp),
+            ErrorCode::CompoundTooSoon
+        );
+
+        update_reward_per_token(rewards_pool, clock.unix_timestamp)?;
+        settle_user_rewards(rewards_pool, user_stake);
+        require!(user_stake.unpaid_rewards > 0, ErrorCode::NoRewardsToCompound);
+
+        let reward_streams =
+            load_reward_streams(rewards_pool, ctx.remaining_accounts, clock.unix_timestamp)?;
+        settle_stream_rewards(&reward_streams, user_stake);
+        exit_reward_streams(&reward_streams)?;
+
+        let amount = take_compoundable_rewards(rewards_pool, user_stake, clock.unix_timestamp);
+        require!(amount > 0, ErrorCode::InsufficientRewardFunds);
+
+        rewards_pool.total_staked += amount;
+        user_stake.staked_amount += amount;
+
+        let rewards_pool = &ctx.accounts.rewards_pool;
+        let seeds = &[
+            b"rewards_pool",
+            rewards_pool.reward_mint.as_ref(),
+            &[rewards_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.stake_vault.to_account_info(),
+            authority: rewards_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        emit!(RewardsCompounded {
+            user: ctx.accounts.user.key(),
+            amount,
+            new_staked_amount: ctx.accounts.user_stake.staked_amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_auto_compound(ctx: Context<SetAutoCompound>, enabled: bool) -> Result<()> {
+        let user_stake = &mut ctx.accounts.user_stake;
+        user_stake.auto_compound = enabled;
+
+        emit!(AutoCompoundToggled {
+            user: ctx.accounts.user.key(),
+            enabled,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_compound_fee(ctx: Context<SetCompoundFee>, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= MAX_COMPOUND_FEE_BPS, ErrorCode::InvalidBasisPoints);
+
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        rewards_pool.compound_fee_bps = fee_bps;
+
+        emit!(CompoundFeeUpdated {
+            pool: rewards_pool.key(),
+            fee_bps,
+        });
+
+        Ok(())
+    }
+
+    /// Compounds every opted-in, due `UserStake` passed in remaining accounts,
+    /// paying the caller `compound_fee_bps` of each compounded amount. The
+    /// pool's reward streams, in index order, precede the stakes.
+    pub fn crank_compound<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CrankCompound<'info>>,
+    ) -> Result<()> {
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        require!(!rewards_pool.emergency_pause, ErrorCode::PoolPaused);
+        require!(
+            rewards_pool.reward_mint == rewards_pool.stake_mint,
+            ErrorCode::CompoundingUnsupported
+        );
+        let stream_count = stream_count(rewards_pool);
+        require!(
+            ctx.remaining_accounts.len() >= stream_count,
+            ErrorCode::InvalidRewardStream
+        );
+        let (stream_accounts, stake_accounts) = ctx.remaining_accounts.split_at(stream_count);
+        require!(
+            stake_accounts.len() <= MAX_COMPOUND_BATCH,
+            ErrorCode::BatchTooLarge
+        );
+
+        let clock = Clock::get()?;
+        update_reward_per_token(rewards_pool, clock.unix_timestamp)?;
+        let reward_streams =
+            load_reward_streams(rewards_pool, stream_accounts, clock.unix_timestamp)?;
+
+        let mut users_compounded = 0u32;
+        let mut total_compounded = 0u64;
+        let mut total_fees = 0u64;
+        for account_info in stake_accounts.iter() {
+            let mut user_stake = Account::<UserStake>::try_from(account_info)?;
+            require_keys_eq!(
+                user_stake.pool,
+                rewards_pool.key(),
+                ErrorCode::InvalidUserStake
+            );
+            if !user_stake.auto_compound
+                || !compound_due(rewards_pool, &user_stake, clock.unix_timestamp)
+            {
+                continue;
+            }
+
+            settle_user_rewards(rewards_pool, &mut user_stake);
+            settle_stream_rewards(&reward_streams, &mut user_stake);
+            let amount =
+                take_compoundable_rewards(rewards_pool, &mut user_stake, clock.unix_timestamp);
+            if amount > 0 {
+                let fee = (amount as u128 * rewards_pool.compound_fee_bps as u128 / 10000) as u64;
+                user_stake.staked_amount += amount - fee;
+
+                users_compounded += 1;
+                total_compounded += amount - fee;
+                total_fees += fee;
+
+                emit!(RewardsCompounded {
+                    user: user_stake.user,
+                    amount: amount - fee,
+                    new_staked_amount: user_stake.staked_amount,
+                });
+            }
+
+            user_stake.exit(&crate::ID)?;
+        }
+
+        rewards_pool.total_staked += total_compounded;
+        exit_reward_streams(&reward_streams)?;
+
+        let rewards_pool = &ctx.accounts.rewards_pool;
+        let seeds = &[
+            b"rewards_pool",
+            rewards_pool.reward_mint.as_ref(),
+            &[rewards_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        if total_compounded > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.stake_vault.to_account_info(),
+                authority: rewards_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, total_compounded)?;
+        }
+
+        if total_fees > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.cranker_reward_account.to_account_info(),
+                authority: rewards_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, total_fees)?;
+        }
+
+        emit!(CompoundCranked {
+            pool: rewards_pool.key(),
+            cranker: ctx.accounts.cranker.key(),
+            users_compounded,
+            amount: total_compounded,
+            fee: total_fees,
+        });
+
+        Ok(())
+    }
+
+    /// Adds an incentive stream in the free slot `index`, paying
+    /// `emission_rate` tokens of `stream_mint` per second, shared pro rata by
+    /// stake, between `start_time` and `end_time`. Emissions are limited to
+    /// what the stream has been funded.
+    pub fn add_reward_stream(
+        ctx: Context<AddRewardStream>,
+        index: u8,
+        emission_rate: u64,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            start_time < end_time && end_time > clock.unix_timestamp,
+            ErrorCode::InvalidStreamSchedule
+        );
+
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        require!(
+            rewards_pool
+                .stream_ids
+                .get(index as usize)
+                .is_some_and(|stream_id| *stream_id == 0),
+            ErrorCode::TooManyRewardStreams
+        );
+
+        let reward_stream = &mut ctx.accounts.reward_stream;
+        reward_stream.pool = rewards_pool.key();
+        reward_stream.index = index;
+        reward_stream.id = rewards_pool.next_stream_id;
+        reward_stream.mint = ctx.accounts.stream_mint.key();
+        reward_stream.vault = ctx.accounts.stream_vault.key();
+        reward_stream.emission_rate = emission_rate;
+        reward_stream.start_time = start_time;
+        reward_stream.end_time = end_time;
+        reward_stream.reward_per_token_stored = 0;
+        reward_stream.last_update_time = clock.unix_timestamp;
+        reward_stream.unallocated_balance = 0;
+        reward_stream.total_distributed = 0;
+        reward_stream.bump = ctx.bumps.reward_stream;
+
+        rewards_pool.stream_ids[index as usize] = reward_stream.id;
+        rewards_pool.next_stream_id += 1;
+
+        emit!(RewardStreamAdded {
+            pool: rewards_pool.key(),
+            stream: reward_stream.key(),
+            mint: reward_stream.mint,
+            emission_rate,
+            start_time,
+            end_time,
+        });
+
+        Ok(())
+    }
+
+    pub fn fund_reward_stream(ctx: Context<FundRewardStream>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            to: ctx.accounts.stream_vault.to_account_info(),
+            authority: ctx.accounts.authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let total_staked = ctx.accounts.rewards_pool.total_staked;
+        let reward_stream = &mut ctx.accounts.reward_stream;
+        update_reward_stream(reward_stream, total_staked, Clock::get()?.unix_timestamp);
+        reward_stream.unallocated_balance += amount;
+
+        emit!(RewardStreamFunded {
+            stream: reward_stream.key(),
+            amount,
+            unallocated_balance: reward_stream.unallocated_balance,
         });
 
         Ok(())
     }
 
-    pub fn compound_rewards(ctx: Context<CompoundRewards>) -> Result<()> {
+    /// Claims every reward stream at once. Remaining accounts: every reward
+    /// stream of the pool in index order, followed by a `(stream_vault,
+    /// user_token_account)` pair per stream in the same order.
+    pub fn claim_stream_rewards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimStreamRewards<'info>>,
+    ) -> Result<()> {
         let rewards_pool = &ctx.accounts.rewards_pool;
         require!(!rewards_pool.emergency_pause, ErrorCode::PoolPaused);
 
-        let user_stake = &mut ctx.accounts.user_stake;
-        let clock = Clock::get()?;
+        let stream_count = stream_count(rewards_pool);
+        require!(
+            ctx.remaining_accounts.len() == stream_count * 3,
+            ErrorCode::InvalidRewardStream
+        );
+        let (stream_accounts, payout_accounts) = ctx.remaining_accounts.split_at(stream_count);
+
+        let clock = Clock::get()?;
+        let mut reward_streams =
+            load_reward_streams(rewards_pool, stream_accounts, clock.unix_timestamp)?;
+        let user_stake = &mut ctx.accounts.user_stake;
+        settle_stream_rewards(&reward_streams, user_stake);
+
+        let seeds = &[
+            b"rewards_pool",
+            rewards_pool.reward_mint.as_ref(),
+            &[rewards_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        for (i, reward_stream) in reward_streams.iter_mut().enumerate() {
+            let stream_vault = &payout_accounts[i * 2];
+            let user_token_account = &payout_accounts[i * 2 + 1];
+            require_keys_eq!(
+                stream_vault.key(),
+                reward_stream.vault,
+                ErrorCode::InvalidRewardStream
+            );
+
+            let checkpoint = &mut user_stake.stream_checkpoints[reward_stream.index as usize];
+            let amount = checkpoint.unpaid_rewards;
+            if amount == 0 {
+                continue;
+            }
+
+            let cpi_accounts = Transfer {
+                from: stream_vault.to_account_info(),
+                to: user_token_account.to_account_info(),
+                authority: rewards_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, amount)?;
+
+            checkpoint.unpaid_rewards = 0;
+            reward_stream.total_distributed += amount;
+
+            emit!(StreamRewardsClaimed {
+                user: ctx.accounts.user.key(),
+                stream: reward_stream.key(),
+                mint: reward_stream.mint,
+                amount,
+            });
+        }
+
+        exit_reward_streams(&reward_streams)?;
+
+        Ok(())
+    }
+
+    /// Closes a stream once `STREAM_CLAIM_WINDOW` has passed since it ended,
+    /// sweeping its vault (unallocated and unclaimed tokens alike) to the
+    /// authority and freeing its slot for a new stream. Rewards not claimed by
+    /// then are forfeited.
+    pub fn close_reward_stream(ctx: Context<CloseRewardStream>) -> Result<()> {
+        let reward_stream = &ctx.accounts.reward_stream;
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= reward_stream.end_time.saturating_add(STREAM_CLAIM_WINDOW),
+            ErrorCode::StreamClaimWindowOpen
+        );
+
+        let rewards_pool = &ctx.accounts.rewards_pool;
+        let seeds = &[
+            b"rewards_pool",
+            rewards_pool.reward_mint.as_ref(),
+            &[rewards_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let swept = ctx.accounts.stream_vault.amount;
+        if swept > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.stream_vault.to_account_info(),
+                to: ctx.accounts.destination_token_account.to_account_info(),
+                authority: rewards_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, swept)?;
+        }
+
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.stream_vault.to_account_info(),
+            destination: ctx.accounts.authority.to_account_info(),
+            authority: rewards_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::close_account(cpi_ctx)?;
+
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        rewards_pool.stream_ids[reward_stream.index as usize] = 0;
+
+        emit!(RewardStreamClosed {
+            pool: rewards_pool.key(),
+            stream: reward_stream.key(),
+            swept,
+        });
+
+        Ok(())
+    }
+
+    /// Rewrites a pool created by the original slot-based program into the
+    /// current layout, growing the account and creating the stake vault. The
+    /// legacy program had no deposits, so there is no accrual to carry over;
+    /// tokens already in the reward vault become the reward balance.
+    pub fn migrate_legacy_pool(ctx: Context<MigrateLegacyPool>) -> Result<()> {
+        let pool_info = ctx.accounts.rewards_pool.to_account_info();
+        let legacy = {
+            let data = pool_info.try_borrow_data()?;
+            require!(
+                data.len() == 8 + LegacyRewardsPool::INIT_SPACE
+                    && data[..8] == RewardsPool::DISCRIMINATOR,
+                ErrorCode::AlreadyMigrated
+            );
+            LegacyRewardsPool::deserialize(&mut &data[8..])?
+        };
+        require_keys_eq!(
+            legacy.authority,
+            ctx.accounts.authority.key(),
+            ErrorCode::Unauthorized
+        );
+        require_keys_eq!(
+            legacy.reward_vault,
+            ctx.accounts.reward_vault.key(),
+            ErrorCode::InvalidRewardVault
+        );
+
+        let space = 8 + RewardsPool::INIT_SPACE;
+        let rent_due = Rent::get()?
+            .minimum_balance(space)
+            .saturating_sub(pool_info.lamports());
+        if rent_due > 0 {
+            let cpi_accounts = system_program::Transfer {
+                from: ctx.accounts.authority.to_account_info(),
+                to: pool_info.clone(),
+            };
+            let cpi_program = ctx.accounts.system_program.to_account_info();
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+            system_program::transfer(cpi_ctx, rent_due)?;
+        }
+        pool_info.realloc(space, true)?;
+
+        let clock = Clock::get()?;
+        let mut rewards_pool = RewardsPool {
+            authority: legacy.authority,
+            guardian: Pubkey::default(),
+            reward_mint: legacy.reward_mint,
+            reward_vault: legacy.reward_vault,
+            stake_mint: ctx.accounts.stake_mint.key(),
+            stake_vault: ctx.accounts.stake_vault.key(),
+            stake_decimals: ctx.accounts.stake_mint.decimals,
+            base_apy: legacy.base_apy,
+            current_apy: legacy.current_apy,
+            min_apy: legacy.min_apy,
+            max_apy: legacy.max_apy,
+            compound_frequency: legacy.compound_frequency,
+            compound_fee_bps: 0,
+            stream_ids: [0; MAX_REWARD_STREAMS],
+            next_stream_id: 1,
+            total_staked: 0,
+            total_rewards_distributed: legacy.total_rewards_distributed,
+            reward_balance: ctx.accounts.reward_vault.amount,
+            total_liabilities: 0,
+            reward_per_token_stored: 0,
+            haircut_enabled: false,
+            staking_pool: Pubkey::default(),
+            min_apy_update_interval: 0,
+            last_apy_update_time: legacy_slot_to_timestamp(legacy.last_update_slot, &clock),
+            apy_curve_model: ApyCurveModel::Piecewise,
+            apy_curve: [ApyCurvePoint::default(); MAX_APY_CURVE_POINTS],
+            apy_curve_len: 0,
+            emergency_pause: legacy.emergency_pause,
+            last_update_time: clock.unix_timestamp,
+            bump: legacy.bump,
+        };
+        store_apy_curve(
+            &mut rewards_pool,
+            &default_apy_curve(legacy.min_apy, legacy.max_apy),
+        )?;
+        rewards_pool.try_serialize(&mut &mut pool_info.try_borrow_mut_data()?[..])?;
+
+        emit!(LegacyPoolMigrated {
+            pool: pool_info.key(),
+            reward_balance: rewards_pool.reward_balance,
+        });
+
+        Ok(())
+    }
+
+    pub fn fund_rewards(ctx: Context<FundRewards>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            to: ctx.accounts.reward_vault.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        update_reward_per_token(rewards_pool, Clock::get()?.unix_timestamp)?;
+        rewards_pool.reward_balance += amount;
+
+        emit!(RewardsFunded {
+            pool: rewards_pool.key(),
+            funder: ctx.accounts.funder.key(),
+            amount,
+            reward_balance: rewards_pool.reward_balance,
+            runway_days: runway_days(rewards_pool, rewards_pool.total_liabilities),
+        });
+
+        Ok(())
+    }
+
+    pub fn set_haircut_mode(ctx: Context<SetHaircutMode>, enabled: bool) -> Result<()> {
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        rewards_pool.haircut_enabled = enabled;
+
+        emit!(HaircutModeUpdated {
+            pool: rewards_pool.key(),
+            enabled,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the number of days of emissions at the current APY that the
+    /// unencumbered reward balance can still cover.
+    pub fn get_runway(ctx: Context<GetRunway>) -> Result<u64> {
+        let rewards_pool = &ctx.accounts.rewards_pool;
+        let total_liabilities = pending_liabilities(rewards_pool, Clock::get()?.unix_timestamp);
+        let runway_days = runway_days(rewards_pool, total_liabilities);
+
+        emit!(RewardsRunway {
+            pool: rewards_pool.key(),
+            reward_balance: rewards_pool.reward_balance,
+            total_liabilities,
+            runway_days,
+        });
+
+        Ok(runway_days)
+    }
+
+    /// Sets the key that may toggle `emergency_pause` alongside the authority
+    /// (`Pubkey::default()` to clear).
+    pub fn set_guardian(ctx: Context<SetGuardian>, guardian: Pubkey) -> Result<()> {
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        rewards_pool.guardian = guardian;
+
+        emit!(GuardianUpdated {
+            pool: rewards_pool.key(),
+            guardian,
+        });
+
+        Ok(())
+    }
+
+    pub fn emergency_pause(ctx: Context<EmergencyPause>, pause: bool) -> Result<()> {
+        let rewards_pool = &mut ctx.accounts.rewards_pool;
+        rewards_pool.emergency_pause = pause;
+
+        emit!(EmergencyPauseToggled {
+            pool: rewards_pool.key(),
+            paused: pause,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeRewardsPool<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RewardsPool::INIT_SPACE,
+        seeds = [b"rewards_pool", reward_mint.key().as_ref()],
+        bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub reward_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = reward_mint,
+        token::authority = rewards_pool,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    pub stake_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stake_mint,
+        token::authority = rewards_pool,
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureAPYUpdates<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump,
+        has_one = authority
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetAPYCurve<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump,
+        has_one = authority
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAPY<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    pub staking_pool: Option<Account<'info, StakingPool>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUserStake<'info> {
+    #[account(
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + UserStake::INIT_SPACE,
+        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
+        bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(mut, address = rewards_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
+        bump = user_stake.bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(mut, address = rewards_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
+        bump = user_stake.bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CloseUserStake<'info> {
+    #[account(
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
+        bump = user_stake.bump,
+        close = user
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(mut, address = rewards_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
+        bump = user_stake.bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub user_reward_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CompoundRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(mut, address = rewards_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(mut, address = rewards_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
+        bump = user_stake.bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetAutoCompound<'info> {
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), user_stake.pool.as_ref()],
+        bump = user_stake.bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCompoundFee<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump,
+        has_one = authority
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CrankCompound<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(mut, address = rewards_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(mut, address = rewards_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub cranker_reward_account: Account<'info, TokenAccount>,
+
+    pub cranker: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u8)]
+pub struct AddRewardStream<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump,
+        has_one = authority
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RewardStream::INIT_SPACE,
+        seeds = [b"reward_stream", rewards_pool.key().as_ref(), &[index]],
+        bump
+    )]
+    pub reward_stream: Account<'info, RewardStream>,
+
+    pub stream_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stream_mint,
+        token::authority = rewards_pool,
+    )]
+    pub stream_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewardStream<'info> {
+    #[account(
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump,
+        has_one = authority
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_stream", rewards_pool.key().as_ref(), &[reward_stream.index]],
+        bump = reward_stream.bump
+    )]
+    pub reward_stream: Account<'info, RewardStream>,
+
+    #[account(mut, address = reward_stream.vault)]
+    pub stream_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub funder_token_account: Account<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimStreamRewards<'info> {
+    #[account(
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
+        bump = user_stake.bump
+    )]
+    pub user_stake: Account<'info, UserStake>,
 
-        let pending_rewards = calculate_rewards(
-            user_stake.staked_amount,
-            rewards_pool.current_apy,
-            user_stake.last_claim_slot,
-            clock.slot,
-        );
+    pub user: Signer<'info>,
 
-        require!(pending_rewards > 0, ErrorCode::NoRewardsToCompound);
+    pub token_program: Program<'info, Token>,
+}
 
-        user_stake.staked_amount += pending_rewards;
-        user_stake.last_claim_slot = clock.slot;
+#[derive(Accounts)]
+pub struct CloseRewardStream<'info> {
+    #[account(
+        mut,
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump,
+        has_one = authority
+    )]
+    pub rewards_pool: Account<'info, RewardsPool>,
 
-        emit!(RewardsCompounded {
-            user: ctx.accounts.user.key(),
-            amount: pending_rewards,
-            new_staked_amount: user_stake.staked_amount,
-        });
+    #[account(
+        mut,
+        seeds = [b"reward_stream", rewards_pool.key().as_ref(), &[reward_stream.index]],
+        bump = reward_stream.bump,
+        close = authority
+    )]
+    pub reward_stream: Account<'info, RewardStream>,
 
-        Ok(())
-    }
+    #[account(mut, address = reward_stream.vault)]
+    pub stream_vault: Account<'info, TokenAccount>,
 
-    pub fn emergency_pause(ctx: Context<EmergencyPause>, pause: bool) -> Result<()> {
-        let rewards_pool = &mut ctx.accounts.rewards_pool;
-        rewards_pool.emergency_pause = pause;
+    #[account(mut)]
+    pub destination_token_account: Account<'info, TokenAccount>,
 
-        emit!(EmergencyPauseToggled {
-            pool: rewards_pool.key(),
-            paused: pause,
-        });
+    #[account(mut)]
+    pub authority: Signer<'info>,
 
-        Ok(())
-    }
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct InitializeRewardsPool<'info> {
+pub struct MigrateLegacyPool<'info> {
+    /// CHECK: Still in the legacy layout; validated and rewritten by the
+    /// instruction.
     #[account(
-        init,
-        payer = authority,
-        space = 8 + RewardsPool::INIT_SPACE,
+        mut,
         seeds = [b"rewards_pool", reward_mint.key().as_ref()],
         bump
     )]
-    pub rewards_pool: Account<'info, RewardsPool>,
+    pub rewards_pool: UncheckedAccount<'info>,
 
     #[account(mut)]
     pub authority: Signer<'info>,
 
     pub reward_mint: Account<'info, Mint>,
 
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    pub stake_mint: Account<'info, Mint>,
+
     #[account(
         init,
         payer = authority,
-        token::mint = reward_mint,
+        token::mint = stake_mint,
         token::authority = rewards_pool,
     )]
-    pub reward_vault: Account<'info, TokenAccount>,
+    pub stake_vault: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
@@ -182,63 +1351,58 @@ pub struct InitializeRewardsPool<'info> {
 }
 
 #[derive(Accounts)]
-pub struct UpdateAPY<'info> {
+pub struct FundRewards<'info> {
     #[account(
         mut,
         seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
-        bump = rewards_pool.bump,
-        has_one = authority
+        bump = rewards_pool.bump
     )]
     pub rewards_pool: Account<'info, RewardsPool>,
 
-    pub authority: Signer<'info>,
+    #[account(mut, address = rewards_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub funder_token_account: Account<'info, TokenAccount>,
+
+    pub funder: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct ClaimRewards<'info> {
+pub struct SetHaircutMode<'info> {
     #[account(
         mut,
         seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
-        bump = rewards_pool.bump
+        bump = rewards_pool.bump,
+        has_one = authority
     )]
     pub rewards_pool: Account<'info, RewardsPool>,
 
-    #[account(mut)]
-    pub reward_vault: Account<'info, TokenAccount>,
-
-    #[account(
-        mut,
-        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
-        bump = user_stake.bump
-    )]
-    pub user_stake: Account<'info, UserStake>,
-
-    #[account(mut)]
-    pub user: Signer<'info>,
-
-    #[account(mut)]
-    pub user_reward_account: Account<'info, TokenAccount>,
-
-    pub token_program: Program<'info, Token>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct CompoundRewards<'info> {
+pub struct GetRunway<'info> {
     #[account(
         seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
         bump = rewards_pool.bump
     )]
     pub rewards_pool: Account<'info, RewardsPool>,
+}
 
+#[derive(Accounts)]
+pub struct SetGuardian<'info> {
     #[account(
         mut,
-        seeds = [b"user_stake", user.key().as_ref(), rewards_pool.key().as_ref()],
-        bump = user_stake.bump
+        seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
+        bump = rewards_pool.bump,
+        has_one = authority
     )]
-    pub user_stake: Account<'info, UserStake>,
+    pub rewards_pool: Account<'info, RewardsPool>,
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+    pub authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
@@ -247,16 +1411,54 @@ pub struct EmergencyPause<'info> {
         mut,
         seeds = [b"rewards_pool", rewards_pool.reward_mint.as_ref()],
         bump = rewards_pool.bump,
-        has_one = authority
+        constraint = authority.key() == rewards_pool.authority
+            || authority.key() == rewards_pool.guardian @ ErrorCode::Unauthorized
     )]
     pub rewards_pool: Account<'info, RewardsPool>,
 
+    /// The pool authority or its guardian.
     pub authority: Signer<'info>,
 }
 
 #[account]
 #[derive(InitSpace)]
 pub struct RewardsPool {
+    pub authority: Pubkey,
+    pub guardian: Pubkey,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub stake_mint: Pubkey,
+    pub stake_vault: Pubkey,
+    pub stake_decimals: u8,
+    pub base_apy: u64,
+    pub current_apy: u64,
+    pub min_apy: u64,
+    pub max_apy: u64,
+    pub compound_frequency: u64,
+    pub compound_fee_bps: u16,
+    /// Id of the stream in each slot, 0 for a free slot.
+    pub stream_ids: [u64; MAX_REWARD_STREAMS],
+    pub next_stream_id: u64,
+    pub total_staked: u64,
+    pub total_rewards_distributed: u64,
+    pub reward_balance: u64,
+    pub total_liabilities: u64,
+    pub reward_per_token_stored: u128,
+    pub haircut_enabled: bool,
+    pub staking_pool: Pubkey,
+    pub min_apy_update_interval: u64,
+    pub last_apy_update_time: i64,
+    pub apy_curve_model: ApyCurveModel,
+    pub apy_curve: [ApyCurvePoint; MAX_APY_CURVE_POINTS],
+    pub apy_curve_len: u8,
+    pub emergency_pause: bool,
+    pub last_update_time: i64,
+    pub bump: u8,
+}
+
+/// `RewardsPool` as written by the original slot-based program.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct LegacyRewardsPool {
     pub authority: Pubkey,
     pub reward_mint: Pubkey,
     pub reward_vault: Pubkey,
@@ -279,10 +1481,68 @@ pub struct UserStake {
     pub pool: Pubkey,
     pub staked_amount: u64,
     pub total_rewards_claimed: u64,
-    pub last_claim_slot: u64,
+    pub unpaid_rewards: u64,
+    pub reward_per_token_paid: u128,
+    pub auto_compound: bool,
+    pub last_compound_time: i64,
+    pub stream_checkpoints: [StreamCheckpoint; MAX_REWARD_STREAMS],
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct RewardStream {
+    pub pool: Pubkey,
+    pub index: u8,
+    pub id: u64,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub emission_rate: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub reward_per_token_stored: u128,
+    pub last_update_time: i64,
+    pub unallocated_balance: u64,
+    pub total_distributed: u64,
     pub bump: u8,
 }
 
+/// A stake's position in one reward stream, indexed like the stream. A
+/// checkpoint left over from a closed stream is reset once the slot's new
+/// stream settles it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct StreamCheckpoint {
+    pub stream_id: u64,
+    pub reward_per_token_paid: u128,
+    pub unpaid_rewards: u64,
+}
+
+/// A TVL breakpoint on the APY curve, with `tvl` in whole stake tokens.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct ApyCurvePoint {
+    pub tvl: u64,
+    pub apy: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ApyCurveModel {
+    Piecewise,
+    Kink,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum ApyCurve {
+    /// Linear interpolation between breakpoints sorted by ascending TVL.
+    Piecewise { points: Vec<ApyCurvePoint> },
+    /// `max_apy` at zero TVL, easing to `kink_apy` at `kink_tvl`, then
+    /// dropping steeply to `min_apy` at `max_tvl`.
+    Kink {
+        kink_tvl: u64,
+        kink_apy: u64,
+        max_tvl: u64,
+    },
+}
+
 #[event]
 pub struct RewardsPoolInitialized {
     pub pool: Pubkey,
@@ -298,11 +1558,40 @@ pub struct APYUpdated {
     pub tvl: u64,
 }
 
+#[event]
+pub struct Deposited {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub staked_amount: u64,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub staked_amount: u64,
+}
+
+#[event]
+pub struct APYUpdateConfigUpdated {
+    pub pool: Pubkey,
+    pub staking_pool: Pubkey,
+    pub min_apy_update_interval: u64,
+}
+
+#[event]
+pub struct APYCurveUpdated {
+    pub pool: Pubkey,
+    pub model: ApyCurveModel,
+    pub points: Vec<ApyCurvePoint>,
+}
+
 #[event]
 pub struct RewardsClaimed {
     pub user: Pubkey,
     pub amount: u64,
-    pub slot: u64,
+    pub forfeited: u64,
+    pub timestamp: i64,
 }
 
 #[event]
@@ -312,6 +1601,94 @@ pub struct RewardsCompounded {
     pub new_staked_amount: u64,
 }
 
+#[event]
+pub struct LegacyPoolMigrated {
+    pub pool: Pubkey,
+    pub reward_balance: u64,
+}
+
+#[event]
+pub struct RewardsFunded {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub reward_balance: u64,
+    pub runway_days: u64,
+}
+
+#[event]
+pub struct HaircutModeUpdated {
+    pub pool: Pubkey,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct RewardsRunway {
+    pub pool: Pubkey,
+    pub reward_balance: u64,
+    pub total_liabilities: u64,
+    pub runway_days: u64,
+}
+
+#[event]
+pub struct AutoCompoundToggled {
+    pub user: Pubkey,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct CompoundFeeUpdated {
+    pub pool: Pubkey,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct CompoundCranked {
+    pub pool: Pubkey,
+    pub cranker: Pubkey,
+    pub users_compounded: u32,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct RewardStreamAdded {
+    pub pool: Pubkey,
+    pub stream: Pubkey,
+    pub mint: Pubkey,
+    pub emission_rate: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+#[event]
+pub struct RewardStreamFunded {
+    pub stream: Pubkey,
+    pub amount: u64,
+    pub unallocated_balance: u64,
+}
+
+#[event]
+pub struct RewardStreamClosed {
+    pub pool: Pubkey,
+    pub stream: Pubkey,
+    pub swept: u64,
+}
+
+#[event]
+pub struct StreamRewardsClaimed {
+    pub user: Pubkey,
+    pub stream: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub pool: Pubkey,
+    pub guardian: Pubkey,
+}
+
 #[event]
 pub struct EmergencyPauseToggled {
     pub pool: Pubkey,
@@ -328,41 +1705,316 @@ pub enum ErrorCode {
     NoRewardsToCompound,
     #[msg("Invalid APY parameters")]
     InvalidAPY,
+    #[msg("APY curve needs 2 to 8 points with strictly increasing TVL")]
+    InvalidAPYCurve,
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Reward vault cannot cover the claim")]
+    InsufficientRewardFunds,
+    #[msg("Insufficient staked balance")]
+    InsufficientStake,
+    #[msg("Stake still holds tokens or unclaimed rewards")]
+    StakeNotEmpty,
+    #[msg("APY was updated too recently")]
+    APYUpdateTooSoon,
+    #[msg("Staking pool does not match the linked TVL source")]
+    InvalidStakingPool,
+    #[msg("Pool is not in the legacy layout")]
+    AlreadyMigrated,
+    #[msg("Reward vault does not match the pool")]
+    InvalidRewardVault,
+    #[msg("Compounding requires the reward mint to be the stake mint")]
+    CompoundingUnsupported,
+    #[msg("Compound frequency has not elapsed")]
+    CompoundTooSoon,
+    #[msg("Invalid basis points")]
+    InvalidBasisPoints,
+    #[msg("Too many stakes in one batch")]
+    BatchTooLarge,
+    #[msg("User stake does not belong to this pool")]
+    InvalidUserStake,
+    #[msg("Reward stream slot is taken or out of range")]
+    TooManyRewardStreams,
+    #[msg("Reward stream accounts are missing, out of order or mismatched")]
+    InvalidRewardStream,
+    #[msg("Reward stream must end after it starts and in the future")]
+    InvalidStreamSchedule,
+    #[msg("Reward stream claim window has not passed")]
+    StreamClaimWindowOpen,
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+}
+
+/// `max_apy` up to 1M staked, easing to `min_apy` at 100M.
+fn default_apy_curve(min_apy: u64, max_apy: u64) -> ApyCurve {
+    ApyCurve::Piecewise {
+        points: vec![
+            ApyCurvePoint {
+                tvl: 1_000_000,
+                apy: max_apy,
+            },
+            ApyCurvePoint {
+                tvl: 100_000_000,
+                apy: min_apy,
+            },
+        ],
+    }
 }
 
-fn calculate_dynamic_apy(base_apy: u64, min_apy: u64, max_apy: u64, tvl: u64) -> u64 {
-    const TVL_THRESHOLD_LOW: u64 = 1_000_000;
-    const TVL_THRESHOLD_HIGH: u64 = 100_000_000;
+fn store_apy_curve(rewards_pool: &mut RewardsPool, curve: &ApyCurve) -> Result<()> {
+    let (model, points) = match curve {
+        ApyCurve::Piecewise { points } => (ApyCurveModel::Piecewise, points.clone()),
+        ApyCurve::Kink {
+            kink_tvl,
+            kink_apy,
+            max_tvl,
+        } => (
+            ApyCurveModel::Kink,
+            vec![
+                ApyCurvePoint {
+                    tvl: 0,
+                    apy: rewards_pool.max_apy,
+                },
+                ApyCurvePoint {
+                    tvl: *kink_tvl,
+                    apy: *kink_apy,
+                },
+                ApyCurvePoint {
+                    tvl: *max_tvl,
+                    apy: rewards_pool.min_apy,
+                },
+            ],
+        ),
+    };
 
-    if tvl < TVL_THRESHOLD_LOW {
-        max_apy
-    } else if tvl > TVL_THRESHOLD_HIGH {
-        min_apy
-    } else {
-        let ratio = (tvl - TVL_THRESHOLD_LOW) * 10000 / (TVL_THRESHOLD_HIGH - TVL_THRESHOLD_LOW);
-        let apy_range = max_apy - min_apy;
-        max_apy - (apy_range * ratio / 10000)
+    require!(
+        points.len() >= 2 && points.len() <= MAX_APY_CURVE_POINTS,
+        ErrorCode::InvalidAPYCurve
+    );
+    require!(
+        points.windows(2).all(|pair| pair[0].tvl < pair[1].tvl),
+        ErrorCode::InvalidAPYCurve
+    );
+    require!(
+        points
+            .iter()
+            .all(|point| point.apy >= rewards_pool.min_apy && point.apy <= rewards_pool.max_apy),
+        ErrorCode::InvalidAPY
+    );
+
+    rewards_pool.apy_curve_model = model;
+    rewards_pool.apy_curve = [ApyCurvePoint::default(); MAX_APY_CURVE_POINTS];
+    rewards_pool.apy_curve[..points.len()].copy_from_slice(&points);
+    rewards_pool.apy_curve_len = points.len() as u8;
+
+    Ok(())
+}
+
+fn calculate_dynamic_apy(rewards_pool: &RewardsPool, tvl: u64) -> u64 {
+    let tvl = tvl / 10u64.pow(rewards_pool.stake_decimals as u32);
+    let points = &rewards_pool.apy_curve[..rewards_pool.apy_curve_len as usize];
+
+    let apy = match points.iter().position(|point| tvl < point.tvl) {
+        Some(0) => points[0].apy,
+        None => points[points.len() - 1].apy,
+        Some(i) => {
+            let (low, high) = (points[i - 1], points[i]);
+            let progress = (tvl - low.tvl) as u128;
+            let span = (high.tvl - low.tvl) as u128;
+            if high.apy >= low.apy {
+                low.apy + ((high.apy - low.apy) as u128 * progress / span) as u64
+            } else {
+                low.apy - ((low.apy - high.apy) as u128 * progress / span) as u64
+            }
+        }
+    };
+
+    apy.clamp(rewards_pool.min_apy, rewards_pool.max_apy)
+}
+
+/// Reward per staked token, scaled by `REWARD_PRECISION`, accrued at `apy`
+/// between two unix timestamps.
+fn reward_per_token_delta(apy: u64, from_time: i64, to_time: i64) -> u128 {
+    let seconds_elapsed = to_time.saturating_sub(from_time).max(0) as u128;
+    (apy as u128 * REWARD_PRECISION)
+        .checked_mul(seconds_elapsed)
+        .unwrap_or(0)
+        / (10000 * SECONDS_PER_YEAR as u128)
+}
+
+fn pending_liabilities(rewards_pool: &RewardsPool, now: i64) -> u64 {
+    let delta = reward_per_token_delta(
+        rewards_pool.current_apy,
+        rewards_pool.last_update_time,
+        now,
+    );
+    let accrued = rewards_pool.total_staked as u128 * delta / REWARD_PRECISION;
+    rewards_pool.total_liabilities.saturating_add(accrued as u64)
+}
+
+fn update_reward_per_token(rewards_pool: &mut RewardsPool, now: i64) -> Result<()> {
+    rewards_pool.total_liabilities = pending_liabilities(rewards_pool, now);
+    rewards_pool.reward_per_token_stored += reward_per_token_delta(
+        rewards_pool.current_apy,
+        rewards_pool.last_update_time,
+        now,
+    );
+    rewards_pool.last_update_time = now;
+
+    Ok(())
+}
+
+/// Moves everything `user_stake` has earned up to the pool's stored
+/// accumulator into `unpaid_rewards`. The pool must be updated first.
+fn settle_user_rewards(rewards_pool: &RewardsPool, user_stake: &mut UserStake) {
+    let earned = user_stake.staked_amount as u128
+        * (rewards_pool.reward_per_token_stored - user_stake.reward_per_token_paid)
+        / REWARD_PRECISION;
+    user_stake.unpaid_rewards += earned as u64;
+    user_stake.reward_per_token_paid = rewards_pool.reward_per_token_stored;
+}
+
+/// Estimates the unix timestamp of a legacy slot-based checkpoint from the
+/// current clock, assuming the nominal slot time it was recorded at.
+fn legacy_slot_to_timestamp(slot: u64, clock: &Clock) -> i64 {
+    let slots_elapsed = clock.slot.saturating_sub(slot) as i64;
+    clock.unix_timestamp - slots_elapsed * LEGACY_SLOT_MS as i64 / 1000
+}
+
+/// Emits a stream's funded rewards for the part of `[start_time, end_time]`
+/// elapsed since its last update.
+fn update_reward_stream(reward_stream: &mut RewardStream, total_staked: u64, now: i64) {
+    let from = reward_stream.last_update_time.max(reward_stream.start_time);
+    let to = now.min(reward_stream.end_time);
+    if to > from && total_staked > 0 {
+        let emitted = (reward_stream.emission_rate as u128 * (to - from) as u128)
+            .min(reward_stream.unallocated_balance as u128);
+        reward_stream.reward_per_token_stored +=
+            emitted * REWARD_PRECISION / total_staked as u128;
+        reward_stream.unallocated_balance -= emitted as u64;
+    }
+    reward_stream.last_update_time = reward_stream.last_update_time.max(now);
+}
+
+fn stream_count(rewards_pool: &RewardsPool) -> usize {
+    rewards_pool
+        .stream_ids
+        .iter()
+        .filter(|stream_id| **stream_id != 0)
+        .count()
+}
+
+/// Loads and updates every reward stream of `rewards_pool`, which must be
+/// passed in slot order.
+fn load_reward_streams<'info>(
+    rewards_pool: &Account<'info, RewardsPool>,
+    accounts: &'info [AccountInfo<'info>],
+    now: i64,
+) -> Result<Vec<Account<'info, RewardStream>>> {
+    require!(
+        accounts.len() == stream_count(rewards_pool),
+        ErrorCode::InvalidRewardStream
+    );
+
+    let mut reward_streams = Vec::with_capacity(accounts.len());
+    let slots = rewards_pool
+        .stream_ids
+        .iter()
+        .enumerate()
+        .filter(|(_, stream_id)| **stream_id != 0);
+    for ((index, stream_id), account_info) in slots.zip(accounts.iter()) {
+        let mut reward_stream = Account::<RewardStream>::try_from(account_info)?;
+        require!(
+            reward_stream.pool == rewards_pool.key()
+                && reward_stream.index as usize == index
+                && reward_stream.id == *stream_id,
+            ErrorCode::InvalidRewardStream
+        );
+        update_reward_stream(&mut reward_stream, rewards_pool.total_staked, now);
+        reward_streams.push(reward_stream);
+    }
+
+    Ok(reward_streams)
+}
+
+fn settle_stream_rewards(reward_streams: &[Account<RewardStream>], user_stake: &mut UserStake) {
+    for reward_stream in reward_streams {
+        let checkpoint = &mut user_stake.stream_checkpoints[reward_stream.index as usize];
+        if checkpoint.stream_id != reward_stream.id {
+            *checkpoint = StreamCheckpoint {
+                stream_id: reward_stream.id,
+                ..StreamCheckpoint::default()
+            };
+        }
+        let earned = user_stake.staked_amount as u128
+            * (reward_stream.reward_per_token_stored - checkpoint.reward_per_token_paid)
+            / REWARD_PRECISION;
+        checkpoint.unpaid_rewards += earned as u64;
+        checkpoint.reward_per_token_paid = reward_stream.reward_per_token_stored;
+    }
+}
+
+fn exit_reward_streams(reward_streams: &[Account<RewardStream>]) -> Result<()> {
+    for reward_stream in reward_streams {
+        reward_stream.exit(&crate::ID)?;
     }
+    Ok(())
+}
+
+fn compound_due(rewards_pool: &RewardsPool, user_stake: &UserStake, now: i64) -> bool {
+    now >= user_stake.last_compound_time + rewards_pool.compound_frequency as i64
 }
 
-fn calculate_rewards(
-    staked_amount: u64,
-    apy: u64,
-    last_claim_slot: u64,
-    current_slot: u64,
+/// Takes as much of a settled stake's unpaid rewards as the pool can pay out of
+/// its reward balance and liabilities, and returns that amount.
+fn take_compoundable_rewards(
+    rewards_pool: &mut RewardsPool,
+    user_stake: &mut UserStake,
+    now: i64,
 ) -> u64 {
-    const SLOTS_PER_YEAR: u64 = 78_840_000;
+    let owed_rewards = user_stake.unpaid_rewards;
+    let total_liabilities = rewards_pool.total_liabilities;
+    let amount = payable_rewards(rewards_pool, total_liabilities, owed_rewards);
+    if amount == 0 {
+        return 0;
+    }
 
-    let slots_elapsed = current_slot.saturating_sub(last_claim_slot);
-    let annual_reward = (staked_amount as u128)
-        .checked_mul(apy as u128)
-        .unwrap_or(0)
-        / 10000;
+    let unpaid_rewards = if rewards_pool.haircut_enabled {
+        0
+    } else {
+        owed_rewards - amount
+    };
 
-    let reward = annual_reward
-        .checked_mul(slots_elapsed as u128)
-        .unwrap_or(0)
-        / SLOTS_PER_YEAR as u128;
+    rewards_pool.total_liabilities = rewards_pool
+        .total_liabilities
+        .saturating_sub(owed_rewards - unpaid_rewards);
+    rewards_pool.reward_balance -= amount;
+
+    user_stake.unpaid_rewards = unpaid_rewards;
+    user_stake.last_compound_time = now;
+
+    amount
+}
+
+/// Caps `owed` at the reward balance, or scales it pro rata against all
+/// outstanding liabilities when the pool is underfunded in haircut mode.
+fn payable_rewards(rewards_pool: &RewardsPool, total_liabilities: u64, owed: u64) -> u64 {
+    let reward_balance = rewards_pool.reward_balance;
+    if rewards_pool.haircut_enabled && total_liabilities > reward_balance {
+        let scaled = owed as u128 * reward_balance as u128 / total_liabilities as u128;
+        (scaled as u64).min(reward_balance)
+    } else {
+        owed.min(reward_balance)
+    }
+}
+
+fn runway_days(rewards_pool: &RewardsPool, total_liabilities: u64) -> u64 {
+    let daily_emissions =
+        rewards_pool.total_staked as u128 * rewards_pool.current_apy as u128 / 10000 / 365;
+    if daily_emissions == 0 {
+        return u64::MAX;
+    }
 
-    reward as u64
+    let free_balance = rewards_pool.reward_balance.saturating_sub(total_liabilities);
+    (free_balance as u128 / daily_emissions) as u64
 }
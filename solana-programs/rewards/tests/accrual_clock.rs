@@ -0,0 +1,156 @@
+use anchor_lang::{prelude::Pubkey, system_program, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use program_test_utils::{
+    create_mint, create_token_account, fetch_account, funded_keypair, mint_to, program_test, send,
+    set_unix_timestamp,
+    solana_program_test::ProgramTestContext,
+    solana_sdk::{
+        instruction::Instruction,
+        signature::{Keypair, Signer},
+        sysvar,
+    },
+};
+use rewards_program::{accounts, instruction, UserStake};
+
+const START_TIME: i64 = 1_700_000_000;
+const SECONDS_PER_YEAR: i64 = 31_536_000;
+const APY_BPS: u64 = 1_000;
+const STAKE_AMOUNT: u64 = 1_000_000;
+
+struct Setup {
+    ctx: ProgramTestContext,
+    user: Keypair,
+    user_token_account: Pubkey,
+    rewards_pool: Pubkey,
+    stake_vault: Pubkey,
+    user_stake: Pubkey,
+}
+
+async fn user_stake(ctx: &mut ProgramTestContext, address: Pubkey) -> UserStake {
+    fetch_account(ctx, address).await
+}
+
+/// Creates a pool paying `APY_BPS` at `START_TIME` and a user staking
+/// `STAKE_AMOUNT` in it at that time.
+async fn setup() -> Setup {
+    let mut ctx = program_test!(rewards_program).start_with_context().await;
+    set_unix_timestamp(&mut ctx, START_TIME).await;
+
+    let user = funded_keypair(&mut ctx).await;
+    let mint = create_mint(&mut ctx, 6).await;
+    let user_token_account = create_token_account(&mut ctx, &mint, &user.pubkey()).await;
+    mint_to(&mut ctx, &mint, &user_token_account, 2 * STAKE_AMOUNT).await;
+    let reward_vault = Keypair::new();
+    let stake_vault = Keypair::new();
+    let payer = ctx.payer.pubkey();
+
+    let (rewards_pool, _) =
+        Pubkey::find_program_address(&[b"rewards_pool", mint.as_ref()], &rewards_program::ID);
+    let (user_stake, _) = Pubkey::find_program_address(
+        &[b"user_stake", user.pubkey().as_ref(), rewards_pool.as_ref()],
+        &rewards_program::ID,
+    );
+
+    let initialize = Instruction {
+        program_id: rewards_program::ID,
+        accounts: accounts::InitializeRewardsPool {
+            rewards_pool,
+            authority: payer,
+            reward_mint: mint,
+            reward_vault: reward_vault.pubkey(),
+            stake_mint: mint,
+            stake_vault: stake_vault.pubkey(),
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeRewardsPool {
+            base_apy: APY_BPS,
+            min_apy: APY_BPS,
+            max_apy: APY_BPS,
+            compound_frequency: 86_400,
+            emergency_pause: false,
+        }
+        .data(),
+    };
+    send(&mut ctx, &[initialize], &[&reward_vault, &stake_vault])
+        .await
+        .unwrap();
+
+    let initialize_user_stake = Instruction {
+        program_id: rewards_program::ID,
+        accounts: accounts::InitializeUserStake {
+            rewards_pool,
+            user_stake,
+            user: user.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeUserStake {}.data(),
+    };
+    let mut setup = Setup {
+        ctx,
+        user,
+        user_token_account,
+        rewards_pool,
+        stake_vault: stake_vault.pubkey(),
+        user_stake,
+    };
+    let deposit = deposit(&setup, STAKE_AMOUNT);
+    send(
+        &mut setup.ctx,
+        &[initialize_user_stake, deposit],
+        &[&setup.user],
+    )
+    .await
+    .unwrap();
+
+    setup
+}
+
+/// The pool has no reward streams, so no remaining accounts are needed.
+fn deposit(setup: &Setup, amount: u64) -> Instruction {
+    Instruction {
+        program_id: rewards_program::ID,
+        accounts: accounts::Deposit {
+            rewards_pool: setup.rewards_pool,
+            stake_vault: setup.stake_vault,
+            user_stake: setup.user_stake,
+            user_token_account: setup.user_token_account,
+            user: setup.user.pubkey(),
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::Deposit { amount }.data(),
+    }
+}
+
+#[tokio::test]
+async fn slots_without_time_accrue_nothing() {
+    let mut setup = setup().await;
+
+    // Roughly a year of slots at 400ms, with the timestamp held still.
+    setup.ctx.warp_to_slot(78_840_000).unwrap();
+    set_unix_timestamp(&mut setup.ctx, START_TIME).await;
+    let ix = deposit(&setup, 1);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    let stake = user_stake(&mut setup.ctx, setup.user_stake).await;
+    assert_eq!(stake.unpaid_rewards, 0);
+}
+
+#[tokio::test]
+async fn time_without_slots_accrues_at_apy() {
+    let mut setup = setup().await;
+    let slot = setup.ctx.banks_client.get_root_slot().await.unwrap();
+
+    // Half a year passes on the clock while the slot does not move.
+    set_unix_timestamp(&mut setup.ctx, START_TIME + SECONDS_PER_YEAR / 2).await;
+    let ix = deposit(&setup, 1);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+    assert_eq!(setup.ctx.banks_client.get_root_slot().await.unwrap(), slot);
+
+    let stake = user_stake(&mut setup.ctx, setup.user_stake).await;
+    assert_eq!(stake.unpaid_rewards, STAKE_AMOUNT * APY_BPS / 10_000 / 2);
+}
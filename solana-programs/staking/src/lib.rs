@@ -1,38 +1,87 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
-
-declare_id!("stkePW8VgQF8CxNm6k7q1FKzGvzRgJ7xNJ8jT6ZVXy");
+use anchor_lang::{system_program, Discriminator};
+use anchor_spl::token::{
+    self, spl_token::instruction::AuthorityType, Burn, Mint, MintTo, SetAuthority, Token,
+    TokenAccount, Transfer,
+};
+
+declare_id!("stkePW8VgQF8CxNm6k7q1FKzGvzRgJ7xNJ8jT6ZVXy1");
+
+/// Fixed-point scale for `StakingPool::acc_reward_per_weight`.
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+/// Fixed-point scale for `StakingPool::share_price`.
+pub const SHARE_PRICE_PRECISION: u128 = 1_000_000_000_000;
+pub const MAX_LOCK_TIERS: usize = 8;
+/// Longest lock a tier may require, in seconds (ten years).
+pub const MAX_LOCK_DURATION: u64 = 10 * 365 * 86_400;
+/// Longest unbonding cooldown a pool may use, in seconds (one year).
+pub const MAX_UNBONDING_PERIOD: u64 = 365 * 86_400;
+/// Virtual assets and shares added to both sides of the receipt exchange
+/// rate so a first depositor cannot inflate the price of a share.
+pub const LIQUID_VIRTUAL_OFFSET: u128 = 1_000_000;
+/// `tier_index` recorded on positions created by `liquid_unstake`.
+pub const LIQUID_TIER_INDEX: u8 = u8::MAX;
 
 #[program]
 pub mod staking_program {
     use super::*;
 
+    /// `generation` tells apart pools of the same mint: 0 for the first pool,
+    /// higher for successor pools that positions can move into with
+    /// `migrate_position`.
     pub fn initialize_staking_pool(
         ctx: Context<InitializeStakingPool>,
-        unbonding_period_7: u64,
-        unbonding_period_14: u64,
-        unbonding_period_30: u64,
-        reward_multiplier_7: u64,
-        reward_multiplier_14: u64,
-        reward_multiplier_30: u64,
+        unbonding_period: u64,
         min_stake_amount: u64,
+        early_unstake_max_penalty_bps: u16,
+        early_unstake_burn_bps: u16,
+        generation: u8,
     ) -> Result<()> {
+        require!(
+            early_unstake_max_penalty_bps <= 10000 && early_unstake_burn_bps <= 10000,
+            ErrorCode::InvalidBasisPoints
+        );
+        require!(
+            unbonding_period <= MAX_UNBONDING_PERIOD,
+            ErrorCode::InvalidUnbondingPeriod
+        );
+
         let staking_pool = &mut ctx.accounts.staking_pool;
         staking_pool.authority = ctx.accounts.authority.key();
+        staking_pool.guardian = Pubkey::default();
         staking_pool.stake_mint = ctx.accounts.stake_mint.key();
         staking_pool.stake_vault = ctx.accounts.stake_vault.key();
-        staking_pool.unbonding_period_7 = unbonding_period_7;
-        staking_pool.unbonding_period_14 = unbonding_period_14;
-        staking_pool.unbonding_period_30 = unbonding_period_30;
-        staking_pool.reward_multiplier_7 = reward_multiplier_7;
-        staking_pool.reward_multiplier_14 = reward_multiplier_14;
-        staking_pool.reward_multiplier_30 = reward_multiplier_30;
+        staking_pool.reward_vault = ctx.accounts.reward_vault.key();
+        staking_pool.insurance_vault = ctx.accounts.insurance_vault.key();
+        staking_pool.unbonding_period = unbonding_period;
         staking_pool.min_stake_amount = min_stake_amount;
+        staking_pool.max_total_staked = 0;
+        staking_pool.max_stake_per_user = 0;
+        staking_pool.early_unstake_max_penalty_bps = early_unstake_max_penalty_bps;
+        staking_pool.early_unstake_burn_bps = early_unstake_burn_bps;
         staking_pool.total_staked = 0;
+        staking_pool.share_price = SHARE_PRICE_PRECISION;
         staking_pool.total_stakers = 0;
-        staking_pool.emergency_pause = false;
+        staking_pool.total_weighted_stake = 0;
+        staking_pool.acc_reward_per_weight = 0;
+        staking_pool.reward_balance = 0;
+        staking_pool.total_rewards_paid = 0;
+        staking_pool.receipt_mint = Pubkey::default();
+        staking_pool.liquid_reward_multiplier = 0;
+        staking_pool.liquid_shares = 0;
+        staking_pool.liquid_reward_debt = 0;
+        staking_pool.slash_max_bps = 0;
+        staking_pool.slash_cooldown = 0;
+        staking_pool.last_slash_time = 0;
+        staking_pool.state = PoolState::Active;
+        staking_pool.generation = generation;
         staking_pool.bump = ctx.bumps.staking_pool;
 
+        let lock_tier_table = &mut ctx.accounts.lock_tier_table;
+        lock_tier_table.pool = staking_pool.key();
+        lock_tier_table.tiers = Vec::new();
+        lock_tier_table.bump = ctx.bumps.lock_tier_table;
+
         emit!(StakingPoolInitialized {
             pool: staking_pool.key(),
             authority: staking_pool.authority,
@@ -42,28 +91,328 @@ pub mod staking_program {
         Ok(())
     }
 
+    pub fn set_early_unstake_config(
+        ctx: Context<UpdateStakingPool>,
+        max_penalty_bps: u16,
+        burn_bps: u16,
+    ) -> Result<()> {
+        require!(
+            max_penalty_bps <= 10000 && burn_bps <= 10000,
+            ErrorCode::InvalidBasisPoints
+        );
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.early_unstake_max_penalty_bps = max_penalty_bps;
+        staking_pool.early_unstake_burn_bps = burn_bps;
+
+        emit!(EarlyUnstakeConfigUpdated {
+            pool: staking_pool.key(),
+            max_penalty_bps,
+            burn_bps,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_min_stake_amount(
+        ctx: Context<UpdateStakingPool>,
+        min_stake_amount: u64,
+    ) -> Result<()> {
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.min_stake_amount = min_stake_amount;
+
+        emit!(StakeLimitsUpdated {
+            pool: staking_pool.key(),
+            min_stake_amount,
+            max_total_staked: staking_pool.max_total_staked,
+            max_stake_per_user: staking_pool.max_stake_per_user,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the pool-wide and per-user stake caps. Zero means uncapped.
+    pub fn set_stake_limits(
+        ctx: Context<UpdateStakingPool>,
+        max_total_staked: u64,
+        max_stake_per_user: u64,
+    ) -> Result<()> {
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.max_total_staked = max_total_staked;
+        staking_pool.max_stake_per_user = max_stake_per_user;
+
+        emit!(StakeLimitsUpdated {
+            pool: staking_pool.key(),
+            min_stake_amount: staking_pool.min_stake_amount,
+            max_total_staked,
+            max_stake_per_user,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the key that may pause the pool or put it into Emergency
+    /// alongside the authority (`Pubkey::default()` to clear).
+    pub fn set_guardian(ctx: Context<UpdateStakingPool>, guardian: Pubkey) -> Result<()> {
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.guardian = guardian;
+
+        emit!(GuardianUpdated {
+            pool: staking_pool.key(),
+            guardian,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_slash_config(
+        ctx: Context<UpdateStakingPool>,
+        max_bps: u16,
+        cooldown: u64,
+    ) -> Result<()> {
+        require!(max_bps < 10000, ErrorCode::InvalidBasisPoints);
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.slash_max_bps = max_bps;
+        staking_pool.slash_cooldown = cooldown;
+
+        emit!(SlashConfigUpdated {
+            pool: staking_pool.key(),
+            max_bps,
+            cooldown,
+        });
+
+        Ok(())
+    }
+
+    /// Takes `bps` of every staked balance by lowering the share price and
+    /// moves the tokens to the insurance fund. Reward weights are share-based,
+    /// so the split of future rewards between stakers is unchanged.
+    pub fn slash(ctx: Context<Slash>, bps: u16, reason_code: u16) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(
+            bps > 0 && bps <= staking_pool.slash_max_bps,
+            ErrorCode::SlashExceedsCap
+        );
+
+        let clock = Clock::get()?;
+        let cooldown_end = i64::try_from(staking_pool.slash_cooldown)
+            .ok()
+            .and_then(|cooldown| staking_pool.last_slash_time.checked_add(cooldown))
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        require!(
+            staking_pool.last_slash_time == 0 || clock.unix_timestamp >= cooldown_end,
+            ErrorCode::SlashCooldownActive
+        );
+
+        let amount = (staking_pool.total_staked as u128)
+            .checked_mul(bps as u128)
+            .ok_or(ErrorCode::ArithmeticOverflow)?
+            / 10000;
+        let amount = u64::try_from(amount).map_err(|_| error!(ErrorCode::ArithmeticOverflow))?;
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let seeds = &[
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation),
+            &[staking_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.stake_vault.to_account_info(),
+            to: ctx.accounts.insurance_vault.to_account_info(),
+            authority: staking_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        let remaining_bps = 10000u128
+            .checked_sub(bps as u128)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        staking_pool.share_price = staking_pool
+            .share_price
+            .checked_mul(remaining_bps)
+            .ok_or(ErrorCode::ArithmeticOverflow)?
+            / 10000;
+        staking_pool.total_staked = staking_pool
+            .total_staked
+            .checked_sub(amount)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        staking_pool.last_slash_time = clock.unix_timestamp;
+
+        emit!(Slashed {
+            pool: staking_pool.key(),
+            bps,
+            amount,
+            share_price: staking_pool.share_price,
+            reason_code,
+        });
+
+        Ok(())
+    }
+
+    pub fn withdraw_insurance_fund(ctx: Context<WithdrawInsuranceFund>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let staking_pool = &ctx.accounts.staking_pool;
+        let seeds = &[
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation),
+            &[staking_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.insurance_vault.to_account_info(),
+            to: ctx.accounts.destination.to_account_info(),
+            authority: staking_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        emit!(InsuranceFundWithdrawn {
+            pool: staking_pool.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn add_lock_tier(
+        ctx: Context<ManageLockTiers>,
+        lock_duration: u64,
+        reward_multiplier: u64,
+    ) -> Result<()> {
+        require!(reward_multiplier > 0, ErrorCode::InvalidLockTier);
+        require!(lock_duration <= MAX_LOCK_DURATION, ErrorCode::InvalidLockTier);
+
+        let lock_tier_table = &mut ctx.accounts.lock_tier_table;
+        require!(
+            lock_tier_table.tiers.len() < MAX_LOCK_TIERS,
+            ErrorCode::TooManyLockTiers
+        );
+
+        lock_tier_table.tiers.push(LockTier {
+            lock_duration,
+            reward_multiplier,
+            enabled: true,
+            capacity: 0,
+            staked_shares: 0,
+        });
+
+        emit!(LockTierUpdated {
+            pool: lock_tier_table.pool,
+            tier_index: (lock_tier_table.tiers.len() - 1) as u8,
+            lock_duration,
+            reward_multiplier,
+            enabled: true,
+            capacity: 0,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_lock_tier_enabled(
+        ctx: Context<ManageLockTiers>,
+        tier_index: u8,
+        enabled: bool,
+    ) -> Result<()> {
+        let lock_tier_table = &mut ctx.accounts.lock_tier_table;
+        let tier = lock_tier_table
+            .tiers
+            .get_mut(tier_index as usize)
+            .ok_or(ErrorCode::InvalidLockTier)?;
+        tier.enabled = enabled;
+        let tier = *tier;
+
+        emit!(LockTierUpdated {
+            pool: lock_tier_table.pool,
+            tier_index,
+            lock_duration: tier.lock_duration,
+            reward_multiplier: tier.reward_multiplier,
+            enabled,
+            capacity: tier.capacity,
+        });
+
+        Ok(())
+    }
+
+    /// Caps the tokens that can be locked in a tier. Zero means uncapped.
+    pub fn set_lock_tier_capacity(
+        ctx: Context<ManageLockTiers>,
+        tier_index: u8,
+        capacity: u64,
+    ) -> Result<()> {
+        let lock_tier_table = &mut ctx.accounts.lock_tier_table;
+        let tier = lock_tier_table
+            .tiers
+            .get_mut(tier_index as usize)
+            .ok_or(ErrorCode::InvalidLockTier)?;
+        tier.capacity = capacity;
+        let tier = *tier;
+
+        emit!(LockTierUpdated {
+            pool: lock_tier_table.pool,
+            tier_index,
+            lock_duration: tier.lock_duration,
+            reward_multiplier: tier.reward_multiplier,
+            enabled: tier.enabled,
+            capacity,
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_user_stake(ctx: Context<InitializeUserStake>) -> Result<()> {
+        let user_stake_account = &mut ctx.accounts.user_stake_account;
+        user_stake_account.user = ctx.accounts.user.key();
+        user_stake_account.pool = ctx.accounts.staking_pool.key();
+        user_stake_account.position_count = 0;
+        user_stake_account.active_positions = 0;
+        user_stake_account.staked_shares = 0;
+        user_stake_account.liquid_shares = 0;
+        user_stake_account.bump = ctx.bumps.user_stake_account;
+
+        Ok(())
+    }
+
+    /// Opens a new position. With `tokenize` the position token is minted in
+    /// the same instruction, so the position is transferable from the start.
     pub fn stake_tokens(
         ctx: Context<StakeTokens>,
         amount: u64,
-        lock_period: LockPeriod,
+        tier_index: u8,
+        tokenize: bool,
     ) -> Result<()> {
         let staking_pool = &ctx.accounts.staking_pool;
-        require!(!staking_pool.emergency_pause, ErrorCode::PoolPaused);
+        require!(staking_pool.state == PoolState::Active, ErrorCode::PoolNotActive);
         require!(amount >= staking_pool.min_stake_amount, ErrorCode::BelowMinStake);
 
-        let clock = Clock::get()?;
-        let unbonding_end = clock.unix_timestamp
-            + match lock_period {
-                LockPeriod::Days7 => staking_pool.unbonding_period_7 as i64,
-                LockPeriod::Days14 => staking_pool.unbonding_period_14 as i64,
-                LockPeriod::Days30 => staking_pool.unbonding_period_30 as i64,
-            };
+        let tier = *ctx
+            .accounts
+            .lock_tier_table
+            .tiers
+            .get(tier_index as usize)
+            .ok_or(ErrorCode::InvalidLockTier)?;
+        require!(tier.enabled, ErrorCode::LockTierDisabled);
+        check_stake_limits(
+            staking_pool,
+            Some(&ctx.accounts.user_stake_account),
+            Some(&tier),
+            amount,
+        )?;
 
-        let reward_multiplier = match lock_period {
-            LockPeriod::Days7 => staking_pool.reward_multiplier_7,
-            LockPeriod::Days14 => staking_pool.reward_multiplier_14,
-            LockPeriod::Days30 => staking_pool.reward_multiplier_30,
-        };
+        let clock = Clock::get()?;
+        let lock_end = add_seconds(clock.unix_timestamp, tier.lock_duration)?;
 
         let cpi_accounts = Transfer {
             from: ctx.accounts.user_token_account.to_account_info(),
@@ -75,53 +424,294 @@ pub mod staking_program {
 
         token::transfer(cpi_ctx, amount)?;
 
+        let shares = amount_to_shares(staking_pool, amount)?;
+        require!(shares > 0, ErrorCode::InvalidAmount);
+
+        let user_stake_account = &mut ctx.accounts.user_stake_account;
         let stake_position = &mut ctx.accounts.stake_position;
         stake_position.user = ctx.accounts.user.key();
         stake_position.pool = staking_pool.key();
-        stake_position.amount = amount;
-        stake_position.lock_period = lock_period;
-        stake_position.reward_multiplier = reward_multiplier;
+        stake_position.index = user_stake_account.position_count;
+        stake_position.shares = shares;
+        stake_position.tier_index = tier_index;
+        stake_position.lock_duration = tier.lock_duration;
+        stake_position.reward_multiplier = tier.reward_multiplier;
         stake_position.stake_timestamp = clock.unix_timestamp;
-        stake_position.unbonding_end = unbonding_end;
+        stake_position.lock_end = lock_end;
+        stake_position.unbonding_end = 0;
         stake_position.is_unbonding = false;
+        stake_position.reward_debt = reward_debt(staking_pool, stake_position)?;
+        stake_position.pending_rewards = 0;
+        stake_position.position_mint = Pubkey::default();
         stake_position.bump = ctx.bumps.stake_position;
 
+        if tokenize {
+            let position_mint = ctx
+                .accounts
+                .position_mint
+                .as_ref()
+                .ok_or(ErrorCode::MissingPositionMint)?;
+            let position_token_account = ctx
+                .accounts
+                .position_token_account
+                .as_ref()
+                .ok_or(ErrorCode::MissingPositionMint)?;
+            mint_position_token(
+                staking_pool,
+                position_mint,
+                position_token_account,
+                &ctx.accounts.token_program,
+            )?;
+            stake_position.position_mint = position_mint.key();
+
+            emit!(PositionTokenized {
+                user: ctx.accounts.user.key(),
+                position: stake_position.key(),
+                position_mint: stake_position.position_mint,
+            });
+        }
+
+        user_stake_account.position_count += 1;
+        user_stake_account.active_positions += 1;
+        user_stake_account.staked_shares += shares;
+        ctx.accounts.lock_tier_table.tiers[tier_index as usize].staked_shares += shares;
+
         let staking_pool = &mut ctx.accounts.staking_pool;
         staking_pool.total_staked += amount;
-        staking_pool.total_stakers += 1;
+        staking_pool.total_weighted_stake += position_weight(stake_position);
+        if user_stake_account.active_positions == 1 {
+            staking_pool.total_stakers += 1;
+        }
 
         emit!(TokensStaked {
             user: ctx.accounts.user.key(),
+            position: stake_position.key(),
+            index: stake_position.index,
             amount,
-            lock_period,
-            unbonding_end,
+            tier_index,
+            lock_end,
         });
 
         Ok(())
     }
 
-    pub fn initiate_unstake(ctx: Context<InitiateUnstake>) -> Result<()> {
+    pub fn increase_stake(ctx: Context<IncreaseStake>, amount: u64) -> Result<()> {
         let staking_pool = &ctx.accounts.staking_pool;
-        require!(!staking_pool.emergency_pause, ErrorCode::PoolPaused);
+        require!(staking_pool.state == PoolState::Active, ErrorCode::PoolNotActive);
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let stake_position = &ctx.accounts.stake_position;
+        check_position_owner(
+            stake_position,
+            ctx.accounts.user.key(),
+            &ctx.accounts.position_token_account,
+        )?;
+        require!(!stake_position.is_unbonding, ErrorCode::AlreadyUnbonding);
+        check_stake_limits(
+            staking_pool,
+            Some(&ctx.accounts.user_stake_account),
+            ctx.accounts
+                .lock_tier_table
+                .tiers
+                .get(stake_position.tier_index as usize),
+            amount,
+        )?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.stake_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let clock = Clock::get()?;
+        let lock_end = add_seconds(clock.unix_timestamp, stake_position.lock_duration)?;
 
         let stake_position = &mut ctx.accounts.stake_position;
+        settle_rewards(staking_pool, stake_position)?;
+
+        let old_weight = position_weight(stake_position);
+        let shares = amount_to_shares(staking_pool, amount)?;
+        stake_position.shares += shares;
+        ctx.accounts.user_stake_account.staked_shares += shares;
+        if let Some(tier) = ctx
+            .accounts
+            .lock_tier_table
+            .tiers
+            .get_mut(stake_position.tier_index as usize)
+        {
+            tier.staked_shares += shares;
+        }
+        stake_position.lock_end = lock_end;
+        stake_position.reward_debt = reward_debt(staking_pool, stake_position)?;
+        let new_amount = shares_to_amount(staking_pool, stake_position.shares)?;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_staked += amount;
+        staking_pool.total_weighted_stake += position_weight(stake_position) - old_weight;
+
+        emit!(StakeIncreased {
+            user: ctx.accounts.user.key(),
+            position: stake_position.key(),
+            amount,
+            new_amount,
+            lock_end,
+        });
+
+        Ok(())
+    }
+
+    pub fn initiate_unstake(ctx: Context<InitiateUnstake>) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state != PoolState::Emergency, ErrorCode::PoolInEmergency);
+
+        let stake_position = &ctx.accounts.stake_position;
+        check_position_owner(
+            stake_position,
+            ctx.accounts.user.key(),
+            &ctx.accounts.position_token_account,
+        )?;
         require!(!stake_position.is_unbonding, ErrorCode::AlreadyUnbonding);
 
-        stake_position.is_unbonding = true;
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= stake_position.lock_end
+                || staking_pool.state == PoolState::Sunset,
+            ErrorCode::LockPeriodNotComplete
+        );
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        let stake_position = &mut ctx.accounts.stake_position;
+        begin_unbonding(staking_pool, stake_position, clock.unix_timestamp)?;
 
         emit!(UnstakeInitiated {
             user: ctx.accounts.user.key(),
-            amount: stake_position.amount,
+            position: stake_position.key(),
+            amount: shares_to_amount(staking_pool, stake_position.shares)?,
+            remaining_staked: 0,
             unbonding_end: stake_position.unbonding_end,
         });
 
         Ok(())
     }
 
-    pub fn complete_unstake(ctx: Context<CompleteUnstake>) -> Result<()> {
+    /// Splits `amount` off a staked position into a new unbonding position,
+    /// leaving the rest staked.
+    pub fn initiate_partial_unstake(
+        ctx: Context<InitiatePartialUnstake>,
+        amount: u64,
+    ) -> Result<()> {
         let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state != PoolState::Emergency, ErrorCode::PoolInEmergency);
+
         let stake_position = &ctx.accounts.stake_position;
+        require!(
+            stake_position.position_mint == Pubkey::default(),
+            ErrorCode::PositionTokenized
+        );
+        require!(!stake_position.is_unbonding, ErrorCode::AlreadyUnbonding);
+
+        let shares = amount_to_shares(staking_pool, amount)?;
+        require!(
+            shares > 0 && shares < stake_position.shares,
+            ErrorCode::InvalidAmount
+        );
+        let remaining_staked = shares_to_amount(staking_pool, stake_position.shares - shares)?;
+        require!(
+            remaining_staked >= staking_pool.min_stake_amount,
+            ErrorCode::BelowMinStake
+        );
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= stake_position.lock_end
+                || staking_pool.state == PoolState::Sunset,
+            ErrorCode::LockPeriodNotComplete
+        );
+
+        let stake_position = &mut ctx.accounts.stake_position;
+        settle_rewards(staking_pool, stake_position)?;
+        stake_position.shares -= shares;
+        stake_position.reward_debt = reward_debt(staking_pool, stake_position)?;
+
+        let user_stake_account = &mut ctx.accounts.user_stake_account;
+        let unbonding_position = &mut ctx.accounts.unbonding_position;
+        unbonding_position.user = stake_position.user;
+        unbonding_position.pool = stake_position.pool;
+        unbonding_position.index = user_stake_account.position_count;
+        unbonding_position.shares = shares;
+        unbonding_position.tier_index = stake_position.tier_index;
+        unbonding_position.lock_duration = stake_position.lock_duration;
+        unbonding_position.reward_multiplier = stake_position.reward_multiplier;
+        unbonding_position.stake_timestamp = stake_position.stake_timestamp;
+        unbonding_position.lock_end = stake_position.lock_end;
+        unbonding_position.unbonding_end = 0;
+        unbonding_position.is_unbonding = false;
+        unbonding_position.pending_rewards = 0;
+        unbonding_position.position_mint = Pubkey::default();
+        unbonding_position.bump = ctx.bumps.unbonding_position;
+        unbonding_position.reward_debt = reward_debt(staking_pool, unbonding_position)?;
+
+        user_stake_account.position_count += 1;
+        user_stake_account.active_positions += 1;
+
+        // The split-off amount is still weighted until begin_unbonding removes it.
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        begin_unbonding(staking_pool, unbonding_position, clock.unix_timestamp)?;
+
+        emit!(UnstakeInitiated {
+            user: ctx.accounts.user.key(),
+            position: unbonding_position.key(),
+            amount,
+            remaining_staked,
+            unbonding_end: unbonding_position.unbonding_end,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_unstake(ctx: Context<CancelUnstake>) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state == PoolState::Active, ErrorCode::PoolNotActive);
+
+        let stake_position = &mut ctx.accounts.stake_position;
+        check_position_owner(
+            stake_position,
+            ctx.accounts.user.key(),
+            &ctx.accounts.position_token_account,
+        )?;
+        require!(stake_position.is_unbonding, ErrorCode::NotUnbonding);
+
+        stake_position.is_unbonding = false;
+        stake_position.unbonding_end = 0;
+        stake_position.reward_debt = reward_debt(staking_pool, stake_position)?;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_weighted_stake += position_weight(stake_position);
+
+        emit!(UnstakeCancelled {
+            user: ctx.accounts.user.key(),
+            position: stake_position.key(),
+            amount: shares_to_amount(staking_pool, stake_position.shares)?,
+            total_weighted_stake: staking_pool.total_weighted_stake,
+        });
+
+        Ok(())
+    }
+
+    pub fn complete_unstake(ctx: Context<CompleteUnstake>) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state != PoolState::Emergency, ErrorCode::PoolInEmergency);
 
+        let stake_position = &ctx.accounts.stake_position;
+        check_position_owner(
+            stake_position,
+            ctx.accounts.user.key(),
+            &ctx.accounts.position_token_account,
+        )?;
         require!(stake_position.is_unbonding, ErrorCode::NotUnbonding);
 
         let clock = Clock::get()?;
@@ -130,9 +720,11 @@ pub mod staking_program {
             ErrorCode::UnbondingPeriodNotComplete
         );
 
+        let amount = shares_to_amount(staking_pool, stake_position.shares)?;
         let seeds = &[
             b"staking_pool",
             staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation),
             &[staking_pool.bump],
         ];
         let signer = &[&seeds[..]];
@@ -145,29 +737,97 @@ pub mod staking_program {
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
 
-        token::transfer(cpi_ctx, stake_position.amount)?;
+        token::transfer(cpi_ctx, amount)?;
+
+        let rewards = stake_position.pending_rewards;
+        if rewards > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: staking_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, rewards)?;
+        }
+
+        burn_position_token(
+            stake_position,
+            &ctx.accounts.position_mint,
+            &ctx.accounts.position_token_account,
+            &ctx.accounts.user,
+            &ctx.accounts.token_program,
+        )?;
+
+        let user_stake_account = &mut ctx.accounts.user_stake_account;
+        user_stake_account.active_positions -= 1;
+        release_position_shares(
+            &mut ctx.accounts.lock_tier_table,
+            user_stake_account,
+            &ctx.accounts.stake_position,
+        );
 
         let staking_pool = &mut ctx.accounts.staking_pool;
-        staking_pool.total_staked -= stake_position.amount;
-        staking_pool.total_stakers -= 1;
+        staking_pool.total_staked -= amount;
+        staking_pool.reward_balance -= rewards;
+        staking_pool.total_rewards_paid += rewards;
+        if user_stake_account.active_positions == 0 {
+            staking_pool.total_stakers -= 1;
+        }
 
         emit!(UnstakeCompleted {
             user: ctx.accounts.user.key(),
-            amount: stake_position.amount,
+            amount,
+            rewards,
         });
 
         Ok(())
     }
 
-    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>) -> Result<()> {
+    /// Releases a position before its lock ends. The penalty is the configured
+    /// maximum scaled by the fraction of the lock still remaining; part of it is
+    /// burned and the rest is redistributed to the remaining stakers.
+    pub fn early_unstake(ctx: Context<EarlyUnstake>) -> Result<()> {
         let staking_pool = &ctx.accounts.staking_pool;
-        require!(staking_pool.emergency_pause, ErrorCode::PoolNotPaused);
+        require!(
+            staking_pool.state == PoolState::Active || staking_pool.state == PoolState::Paused,
+            ErrorCode::PoolNotActive
+        );
 
         let stake_position = &ctx.accounts.stake_position;
+        check_position_owner(
+            stake_position,
+            ctx.accounts.user.key(),
+            &ctx.accounts.position_token_account,
+        )?;
+        require!(!stake_position.is_unbonding, ErrorCode::AlreadyUnbonding);
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp < stake_position.lock_end,
+            ErrorCode::LockPeriodComplete
+        );
 
+        let amount = shares_to_amount(staking_pool, stake_position.shares)?;
+        let penalty =
+            early_unstake_penalty(staking_pool, stake_position, amount, clock.unix_timestamp)?;
+        let burned = (penalty as u128 * staking_pool.early_unstake_burn_bps as u128 / 10000) as u64;
+        let redistributed = penalty - burned;
+
+        // Take the position out of the weighted stake before redistributing so
+        // the leaver does not earn a share of its own penalty.
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        let stake_position = &mut ctx.accounts.stake_position;
+        settle_rewards(staking_pool, stake_position)?;
+        staking_pool.total_weighted_stake -= position_weight(stake_position);
+        let rewards = stake_position.pending_rewards;
+
+        let staking_pool = &ctx.accounts.staking_pool;
         let seeds = &[
             b"staking_pool",
             staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation),
             &[staking_pool.bump],
         ];
         let signer = &[&seeds[..]];
@@ -180,56 +840,1928 @@ pub mod staking_program {
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
 
-        token::transfer(cpi_ctx, stake_position.amount)?;
+        token::transfer(cpi_ctx, amount - penalty)?;
 
-        let staking_pool = &mut ctx.accounts.staking_pool;
-        staking_pool.total_staked -= stake_position.amount;
-        staking_pool.total_stakers -= 1;
+        if rewards > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: staking_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
 
-        emit!(EmergencyWithdrawal {
+            token::transfer(cpi_ctx, rewards)?;
+        }
+
+        if burned > 0 {
+            let cpi_accounts = Burn {
+                mint: ctx.accounts.stake_mint.to_account_info(),
+                from: ctx.accounts.stake_vault.to_account_info(),
+                authority: staking_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::burn(cpi_ctx, burned)?;
+        }
+
+        if redistributed > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.stake_vault.to_account_info(),
+                to: ctx.accounts.reward_vault.to_account_info(),
+                authority: staking_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, redistributed)?;
+        }
+
+        burn_position_token(
+            &ctx.accounts.stake_position,
+            &ctx.accounts.position_mint,
+            &ctx.accounts.position_token_account,
+            &ctx.accounts.user,
+            &ctx.accounts.token_program,
+        )?;
+
+        let user_stake_account = &mut ctx.accounts.user_stake_account;
+        user_stake_account.active_positions -= 1;
+        release_position_shares(
+            &mut ctx.accounts.lock_tier_table,
+            user_stake_account,
+            &ctx.accounts.stake_position,
+        );
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_staked -= amount;
+        if user_stake_account.active_positions == 0 {
+            staking_pool.total_stakers -= 1;
+        }
+        staking_pool.reward_balance -= rewards;
+        staking_pool.total_rewards_paid += rewards;
+        distribute_rewards(staking_pool, redistributed)?;
+
+        emit!(EarlyUnstaked {
+            user: ctx.accounts.user.key(),
+            position: ctx.accounts.stake_position.key(),
+            amount,
+            penalty,
+            burned,
+            redistributed,
+            rewards,
+        });
+
+        Ok(())
+    }
+
+    pub fn emergency_withdraw(ctx: Context<EmergencyWithdraw>) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state == PoolState::Emergency, ErrorCode::PoolNotInEmergency);
+
+        let stake_position = &mut ctx.accounts.stake_position;
+        check_position_owner(
+            stake_position,
+            ctx.accounts.user.key(),
+            &ctx.accounts.position_token_account,
+        )?;
+        settle_rewards(staking_pool, stake_position)?;
+
+        let amount = shares_to_amount(staking_pool, stake_position.shares)?;
+        let seeds = &[
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation),
+            &[staking_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.stake_vault.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: staking_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        burn_position_token(
+            stake_position,
+            &ctx.accounts.position_mint,
+            &ctx.accounts.position_token_account,
+            &ctx.accounts.user,
+            &ctx.accounts.token_program,
+        )?;
+
+        let user_stake_account = &mut ctx.accounts.user_stake_account;
+        user_stake_account.active_positions -= 1;
+        release_position_shares(
+            &mut ctx.accounts.lock_tier_table,
+            user_stake_account,
+            stake_position,
+        );
+
+        // Unclaimed rewards are forfeited and picked up again by the next sync.
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_staked -= amount;
+        staking_pool.reward_balance -= stake_position.pending_rewards;
+        if !stake_position.is_unbonding {
+            staking_pool.total_weighted_stake -= position_weight(stake_position);
+        }
+        if user_stake_account.active_positions == 0 {
+            staking_pool.total_stakers -= 1;
+        }
+
+        emit!(EmergencyWithdrawal {
+            user: ctx.accounts.user.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn fund_staking_rewards(ctx: Context<FundStakingRewards>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.funder_token_account.to_account_info(),
+            to: ctx.accounts.reward_vault.to_account_info(),
+            authority: ctx.accounts.funder.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        ctx.accounts.reward_vault.reload()?;
+        let vault_balance = ctx.accounts.reward_vault.amount;
+        let distributed = sync_rewards(&mut ctx.accounts.staking_pool, vault_balance)?;
+
+        emit!(StakingRewardsFunded {
+            pool: ctx.accounts.staking_pool.key(),
+            funder: ctx.accounts.funder.key(),
+            amount,
+            distributed,
+        });
+
+        Ok(())
+    }
+
+    /// Distributes tokens that reached the reward vault without going through
+    /// `fund_staking_rewards`, such as holder-reward tax inflows.
+    pub fn sync_staking_rewards(ctx: Context<SyncStakingRewards>) -> Result<()> {
+        let vault_balance = ctx.accounts.reward_vault.amount;
+        let distributed = sync_rewards(&mut ctx.accounts.staking_pool, vault_balance)?;
+
+        emit!(StakingRewardsSynced {
+            pool: ctx.accounts.staking_pool.key(),
+            distributed,
+            acc_reward_per_weight: ctx.accounts.staking_pool.acc_reward_per_weight,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_staking_rewards(ctx: Context<ClaimStakingRewards>) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state != PoolState::Emergency, ErrorCode::PoolInEmergency);
+
+        let stake_position = &mut ctx.accounts.stake_position;
+        check_position_owner(
+            stake_position,
+            ctx.accounts.user.key(),
+            &ctx.accounts.position_token_account,
+        )?;
+
+        settle_rewards(staking_pool, stake_position)?;
+        stake_position.reward_debt = reward_debt(staking_pool, stake_position)?;
+
+        let rewards = stake_position.pending_rewards;
+        require!(rewards > 0, ErrorCode::NoRewardsToClaim);
+
+        let seeds = &[
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation),
+            &[staking_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: staking_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, rewards)?;
+
+        stake_position.pending_rewards = 0;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.reward_balance -= rewards;
+        staking_pool.total_rewards_paid += rewards;
+
+        emit!(StakingRewardsClaimed {
+            user: ctx.accounts.user.key(),
+            position: stake_position.key(),
+            amount: rewards,
+        });
+
+        Ok(())
+    }
+
+    /// Re-locks a position in place into `tier_index`, restarting its lock.
+    /// The new lock may not end earlier or be shorter than the current one.
+    /// To move into another pool of the same mint, use `migrate_position`.
+    pub fn relock_position(ctx: Context<RelockPosition>, tier_index: u8) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state == PoolState::Active, ErrorCode::PoolNotActive);
+
+        let stake_position = &ctx.accounts.stake_position;
+        check_position_owner(
+            stake_position,
+            ctx.accounts.user.key(),
+            &ctx.accounts.position_token_account,
+        )?;
+        require!(!stake_position.is_unbonding, ErrorCode::AlreadyUnbonding);
+
+        let tier = *ctx
+            .accounts
+            .lock_tier_table
+            .tiers
+            .get(tier_index as usize)
+            .ok_or(ErrorCode::InvalidLockTier)?;
+        require!(tier.enabled, ErrorCode::LockTierDisabled);
+
+        let clock = Clock::get()?;
+        let lock_end = add_seconds(clock.unix_timestamp, tier.lock_duration)?;
+        require!(
+            tier.lock_duration >= stake_position.lock_duration && lock_end >= stake_position.lock_end,
+            ErrorCode::LockShortened
+        );
+
+        let amount = shares_to_amount(staking_pool, stake_position.shares)?;
+        if tier_index != stake_position.tier_index && tier.capacity > 0 {
+            let staked = shares_to_amount(staking_pool, tier.staked_shares)?;
+            require!(
+                staked as u128 + amount as u128 <= tier.capacity as u128,
+                ErrorCode::LockTierCapacityExceeded
+            );
+        }
+
+        let stake_position = &mut ctx.accounts.stake_position;
+        settle_rewards(staking_pool, stake_position)?;
+
+        let old_weight = position_weight(stake_position);
+        let tiers = &mut ctx.accounts.lock_tier_table.tiers;
+        if let Some(old_tier) = tiers.get_mut(stake_position.tier_index as usize) {
+            old_tier.staked_shares = old_tier.staked_shares.saturating_sub(stake_position.shares);
+        }
+        tiers[tier_index as usize].staked_shares += stake_position.shares;
+
+        stake_position.tier_index = tier_index;
+        stake_position.lock_duration = tier.lock_duration;
+        stake_position.reward_multiplier = tier.reward_multiplier;
+        stake_position.lock_end = lock_end;
+        stake_position.reward_debt = reward_debt(staking_pool, stake_position)?;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_weighted_stake =
+            staking_pool.total_weighted_stake - old_weight + position_weight(stake_position);
+
+        emit!(PositionRelocked {
+            user: ctx.accounts.user.key(),
+            position: stake_position.key(),
+            tier_index,
+            lock_end,
+        });
+
+        Ok(())
+    }
+
+    /// Moves a position's tokens and pending rewards vault-to-vault into a new
+    /// position in another pool of the same mint, such as a successor pool
+    /// after an upgrade, skipping the unbonding period. The destination lock
+    /// may not end earlier or be shorter.
+    pub fn migrate_position(ctx: Context<MigratePosition>, tier_index: u8) -> Result<()> {
+        let source_pool = &ctx.accounts.source_pool;
+        let destination_pool = &ctx.accounts.destination_pool;
+        require!(
+            source_pool.state != PoolState::Emergency,
+            ErrorCode::PoolInEmergency
+        );
+        require!(
+            destination_pool.state == PoolState::Active,
+            ErrorCode::PoolNotActive
+        );
+
+        let stake_position = &ctx.accounts.stake_position;
+        require!(
+            stake_position.position_mint == Pubkey::default(),
+            ErrorCode::PositionTokenized
+        );
+        require!(!stake_position.is_unbonding, ErrorCode::AlreadyUnbonding);
+
+        let tier = *ctx
+            .accounts
+            .destination_lock_tier_table
+            .tiers
+            .get(tier_index as usize)
+            .ok_or(ErrorCode::InvalidLockTier)?;
+        require!(tier.enabled, ErrorCode::LockTierDisabled);
+
+        let clock = Clock::get()?;
+        let lock_end = add_seconds(clock.unix_timestamp, tier.lock_duration)?;
+        require!(
+            tier.lock_duration >= stake_position.lock_duration && lock_end >= stake_position.lock_end,
+            ErrorCode::LockShortened
+        );
+
+        let amount = shares_to_amount(source_pool, stake_position.shares)?;
+        require!(
+            amount >= destination_pool.min_stake_amount,
+            ErrorCode::BelowMinStake
+        );
+        check_stake_limits(
+            destination_pool,
+            Some(&ctx.accounts.destination_user_stake_account),
+            Some(&tier),
+            amount,
+        )?;
+
+        let stake_position = &mut ctx.accounts.stake_position;
+        settle_rewards(source_pool, stake_position)?;
+        let rewards = stake_position.pending_rewards;
+
+        let seeds = &[
+            b"staking_pool",
+            source_pool.stake_mint.as_ref(),
+            generation_seed(&source_pool.generation),
+            &[source_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.source_stake_vault.to_account_info(),
+            to: ctx.accounts.destination_stake_vault.to_account_info(),
+            authority: source_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        if rewards > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.source_reward_vault.to_account_info(),
+                to: ctx.accounts.destination_reward_vault.to_account_info(),
+                authority: source_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, rewards)?;
+        }
+
+        let source_user_stake_account = &mut ctx.accounts.source_user_stake_account;
+        source_user_stake_account.active_positions -= 1;
+        release_position_shares(
+            &mut ctx.accounts.source_lock_tier_table,
+            source_user_stake_account,
+            stake_position,
+        );
+
+        let source_pool = &mut ctx.accounts.source_pool;
+        source_pool.total_staked -= amount;
+        source_pool.total_weighted_stake -= position_weight(stake_position);
+        source_pool.reward_balance -= rewards;
+        if source_user_stake_account.active_positions == 0 {
+            source_pool.total_stakers -= 1;
+        }
+
+        let destination_pool = &ctx.accounts.destination_pool;
+        let shares = amount_to_shares(destination_pool, amount)?;
+        require!(shares > 0, ErrorCode::InvalidAmount);
+
+        let destination_user_stake_account = &mut ctx.accounts.destination_user_stake_account;
+        let new_position = &mut ctx.accounts.new_position;
+        new_position.user = ctx.accounts.user.key();
+        new_position.pool = destination_pool.key();
+        new_position.index = destination_user_stake_account.position_count;
+        new_position.shares = shares;
+        new_position.tier_index = tier_index;
+        new_position.lock_duration = tier.lock_duration;
+        new_position.reward_multiplier = tier.reward_multiplier;
+        new_position.stake_timestamp = stake_position.stake_timestamp;
+        new_position.lock_end = lock_end;
+        new_position.unbonding_end = 0;
+        new_position.is_unbonding = false;
+        new_position.reward_debt = reward_debt(destination_pool, new_position)?;
+        new_position.pending_rewards = rewards;
+        new_position.position_mint = Pubkey::default();
+        new_position.bump = ctx.bumps.new_position;
+
+        destination_user_stake_account.position_count += 1;
+        destination_user_stake_account.active_positions += 1;
+        destination_user_stake_account.staked_shares += shares;
+        ctx.accounts.destination_lock_tier_table.tiers[tier_index as usize].staked_shares += shares;
+
+        let destination_pool = &mut ctx.accounts.destination_pool;
+        destination_pool.total_staked += amount;
+        destination_pool.total_weighted_stake += position_weight(new_position);
+        destination_pool.reward_balance += rewards;
+        if destination_user_stake_account.active_positions == 1 {
+            destination_pool.total_stakers += 1;
+        }
+
+        emit!(PositionMigrated {
+            user: ctx.accounts.user.key(),
+            source_position: stake_position.key(),
+            new_position: new_position.key(),
+            destination_pool: destination_pool.key(),
+            amount,
+            rewards,
+            lock_end,
+        });
+
+        Ok(())
+    }
+
+    /// Mints a 1-of-1 token for a position. From then on whoever holds the
+    /// token owns the position, instead of the staker who opened it.
+    pub fn tokenize_position(ctx: Context<TokenizePosition>) -> Result<()> {
+        mint_position_token(
+            &ctx.accounts.staking_pool,
+            &ctx.accounts.position_mint,
+            &ctx.accounts.position_token_account,
+            &ctx.accounts.token_program,
+        )?;
+
+        let stake_position = &mut ctx.accounts.stake_position;
+        stake_position.position_mint = ctx.accounts.position_mint.key();
+
+        emit!(PositionTokenized {
+            user: ctx.accounts.user.key(),
+            position: stake_position.key(),
+            position_mint: stake_position.position_mint,
+        });
+
+        Ok(())
+    }
+
+    pub fn enable_liquid_staking(
+        ctx: Context<EnableLiquidStaking>,
+        reward_multiplier: u64,
+    ) -> Result<()> {
+        require!(reward_multiplier > 0, ErrorCode::InvalidLockTier);
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.receipt_mint = ctx.accounts.receipt_mint.key();
+        staking_pool.liquid_reward_multiplier = reward_multiplier;
+
+        emit!(LiquidStakingEnabled {
+            pool: staking_pool.key(),
+            receipt_mint: staking_pool.receipt_mint,
+            reward_multiplier,
+        });
+
+        Ok(())
+    }
+
+    /// Stakes into the pool's liquid share and mints receipt tokens at the
+    /// current exchange rate.
+    pub fn liquid_stake(ctx: Context<LiquidStake>, amount: u64) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state == PoolState::Active, ErrorCode::PoolNotActive);
+        require!(amount >= staking_pool.min_stake_amount, ErrorCode::BelowMinStake);
+        check_stake_limits(
+            staking_pool,
+            Some(&ctx.accounts.user_stake_account),
+            None,
+            amount,
+        )?;
+
+        let stake_mint = staking_pool.stake_mint;
+        let generation = staking_pool.generation;
+        let bump = staking_pool.bump;
+        let seeds = &[
+            b"staking_pool",
+            stake_mint.as_ref(),
+            generation_seed(&generation),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let compounded = accrue_liquid_rewards(&mut ctx.accounts.staking_pool)?;
+        if compounded > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.stake_vault.to_account_info(),
+                authority: ctx.accounts.staking_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, compounded)?;
+        }
+
+        let receipt_amount = receipt_for_amount(
+            &ctx.accounts.staking_pool,
+            ctx.accounts.receipt_mint.supply,
+            amount,
+        )?;
+        let shares = amount_to_shares(&ctx.accounts.staking_pool, amount)?;
+        require!(receipt_amount > 0 && shares > 0, ErrorCode::InvalidAmount);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.stake_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.receipt_mint.to_account_info(),
+            to: ctx.accounts.user_receipt_account.to_account_info(),
+            authority: ctx.accounts.staking_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::mint_to(cpi_ctx, receipt_amount)?;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.liquid_shares += shares;
+        staking_pool.total_staked += amount;
+        staking_pool.total_weighted_stake +=
+            shares as u128 * staking_pool.liquid_reward_multiplier as u128;
+        staking_pool.liquid_reward_debt = liquid_reward_debt(staking_pool)?;
+        ctx.accounts.user_stake_account.liquid_shares += shares;
+
+        emit!(LiquidStaked {
+            user: ctx.accounts.user.key(),
+            amount,
+            receipt_amount,
+            liquid_total_assets: shares_to_amount(staking_pool, staking_pool.liquid_shares)?,
+        });
+
+        Ok(())
+    }
+
+    /// Burns receipt tokens and moves the underlying tokens into a new
+    /// unbonding position, withdrawn with `complete_unstake` after the cooldown.
+    pub fn liquid_unstake(ctx: Context<LiquidUnstake>, receipt_amount: u64) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state != PoolState::Emergency, ErrorCode::PoolInEmergency);
+        require!(receipt_amount > 0, ErrorCode::InvalidAmount);
+
+        let stake_mint = staking_pool.stake_mint;
+        let generation = staking_pool.generation;
+        let bump = staking_pool.bump;
+        let seeds = &[
+            b"staking_pool",
+            stake_mint.as_ref(),
+            generation_seed(&generation),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let compounded = accrue_liquid_rewards(&mut ctx.accounts.staking_pool)?;
+        if compounded > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.stake_vault.to_account_info(),
+                authority: ctx.accounts.staking_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, compounded)?;
+        }
+
+        let amount = amount_for_receipt(
+            &ctx.accounts.staking_pool,
+            ctx.accounts.receipt_mint.supply,
+            receipt_amount,
+        )?;
+        let shares = amount_to_shares(&ctx.accounts.staking_pool, amount)?;
+        require!(shares > 0, ErrorCode::InvalidAmount);
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.receipt_mint.to_account_info(),
+            from: ctx.accounts.user_receipt_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::burn(cpi_ctx, receipt_amount)?;
+
+        let clock = Clock::get()?;
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.liquid_shares -= shares;
+        staking_pool.total_weighted_stake -=
+            shares as u128 * staking_pool.liquid_reward_multiplier as u128;
+        staking_pool.liquid_reward_debt = liquid_reward_debt(staking_pool)?;
+
+        let user_stake_account = &mut ctx.accounts.user_stake_account;
+        let stake_position = &mut ctx.accounts.stake_position;
+        stake_position.user = ctx.accounts.user.key();
+        stake_position.pool = staking_pool.key();
+        stake_position.index = user_stake_account.position_count;
+        stake_position.shares = shares;
+        stake_position.tier_index = LIQUID_TIER_INDEX;
+        stake_position.lock_duration = 0;
+        stake_position.reward_multiplier = staking_pool.liquid_reward_multiplier;
+        stake_position.stake_timestamp = clock.unix_timestamp;
+        stake_position.lock_end = clock.unix_timestamp;
+        stake_position.unbonding_end =
+            add_seconds(clock.unix_timestamp, staking_pool.unbonding_period)?;
+        stake_position.is_unbonding = true;
+        stake_position.reward_debt = 0;
+        stake_position.pending_rewards = 0;
+        stake_position.position_mint = Pubkey::default();
+        stake_position.bump = ctx.bumps.stake_position;
+
+        user_stake_account.position_count += 1;
+        user_stake_account.active_positions += 1;
+        user_stake_account.staked_shares += shares;
+        // Receipts are transferable, so the burner may hold more than they staked.
+        user_stake_account.liquid_shares = user_stake_account.liquid_shares.saturating_sub(shares);
+        if user_stake_account.active_positions == 1 {
+            staking_pool.total_stakers += 1;
+        }
+
+        emit!(LiquidUnstaked {
             user: ctx.accounts.user.key(),
-            amount: stake_position.amount,
+            position: stake_position.key(),
+            receipt_amount,
+            amount,
+            unbonding_end: stake_position.unbonding_end,
         });
 
-        Ok(())
-    }
+        Ok(())
+    }
+
+    /// Emergency exit for liquid stakers: burns receipt tokens and pays out
+    /// the underlying tokens immediately, without an unbonding position.
+    pub fn emergency_liquid_withdraw(
+        ctx: Context<EmergencyLiquidWithdraw>,
+        receipt_amount: u64,
+    ) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        require!(staking_pool.state == PoolState::Emergency, ErrorCode::PoolNotInEmergency);
+        require!(receipt_amount > 0, ErrorCode::InvalidAmount);
+
+        let stake_mint = staking_pool.stake_mint;
+        let generation = staking_pool.generation;
+        let bump = staking_pool.bump;
+        let seeds = &[
+            b"staking_pool",
+            stake_mint.as_ref(),
+            generation_seed(&generation),
+            &[bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let compounded = accrue_liquid_rewards(&mut ctx.accounts.staking_pool)?;
+        if compounded > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.stake_vault.to_account_info(),
+                authority: ctx.accounts.staking_pool.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token::transfer(cpi_ctx, compounded)?;
+        }
+
+        let amount = amount_for_receipt(
+            &ctx.accounts.staking_pool,
+            ctx.accounts.receipt_mint.supply,
+            receipt_amount,
+        )?;
+        let shares = amount_to_shares(&ctx.accounts.staking_pool, amount)?;
+        require!(shares > 0, ErrorCode::InvalidAmount);
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.receipt_mint.to_account_info(),
+            from: ctx.accounts.user_receipt_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token::burn(cpi_ctx, receipt_amount)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.stake_vault.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.staking_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, amount)?;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.liquid_shares -= shares;
+        staking_pool.total_staked -= amount;
+        staking_pool.total_weighted_stake -=
+            shares as u128 * staking_pool.liquid_reward_multiplier as u128;
+        staking_pool.liquid_reward_debt = liquid_reward_debt(staking_pool)?;
+
+        if let Some(user_stake_account) = &mut ctx.accounts.user_stake_account {
+            user_stake_account.liquid_shares =
+                user_stake_account.liquid_shares.saturating_sub(shares);
+        }
+
+        emit!(EmergencyWithdrawal {
+            user: ctx.accounts.user.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Permissionless crank that folds accrued rewards into the liquid share,
+    /// raising the receipt exchange rate.
+    pub fn compound_liquid_rewards(ctx: Context<CompoundLiquidRewards>) -> Result<()> {
+        let compounded = accrue_liquid_rewards(&mut ctx.accounts.staking_pool)?;
+        require!(compounded > 0, ErrorCode::NoRewardsToClaim);
+
+        let staking_pool = &ctx.accounts.staking_pool;
+        let seeds = &[
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation),
+            &[staking_pool.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.stake_vault.to_account_info(),
+            authority: staking_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token::transfer(cpi_ctx, compounded)?;
+
+        emit!(LiquidRewardsCompounded {
+            pool: staking_pool.key(),
+            amount: compounded,
+            liquid_total_assets: shares_to_amount(staking_pool, staking_pool.liquid_shares)?,
+        });
+
+        Ok(())
+    }
+
+    /// Moves the pool between states. Paused stops deposits but keeps normal
+    /// exits, Emergency only allows `emergency_withdraw` and
+    /// `emergency_liquid_withdraw`, and Sunset retires the pool for good:
+    /// deposits are rejected and locks no longer apply.
+    /// The guardian may do everything except sunset the pool.
+    pub fn set_pool_state(ctx: Context<SetPoolState>, state: PoolState) -> Result<()> {
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        require!(staking_pool.state != PoolState::Sunset, ErrorCode::PoolSunset);
+        if ctx.accounts.authority.key() != staking_pool.authority {
+            require!(state != PoolState::Sunset, ErrorCode::Unauthorized);
+        }
+
+        let previous_state = staking_pool.state;
+        staking_pool.state = state;
+
+        emit!(PoolStateChanged {
+            pool: staking_pool.key(),
+            previous_state,
+            state,
+        });
+
+        Ok(())
+    }
+
+    /// Rewrites a pool created by the original fixed-lock program into the
+    /// current layout, growing the account and creating the vaults and the
+    /// lock tier table. The 7/14/30-day locks become tiers 0, 1 and 2. Legacy
+    /// positions earn rewards and count as stakers once they are migrated with
+    /// `migrate_legacy_position`. The legacy program had no cooldown, so the
+    /// pool's `unbonding_period` is set here.
+    pub fn migrate_legacy_pool(
+        ctx: Context<MigrateLegacyPool>,
+        unbonding_period: u64,
+    ) -> Result<()> {
+        require!(
+            unbonding_period <= MAX_UNBONDING_PERIOD,
+            ErrorCode::InvalidUnbondingPeriod
+        );
+
+        let pool_info = ctx.accounts.staking_pool.to_account_info();
+        let legacy = {
+            let data = pool_info.try_borrow_data()?;
+            require!(
+                data.len() == 8 + LegacyStakingPool::INIT_SPACE
+                    && data[..8] == StakingPool::DISCRIMINATOR,
+                ErrorCode::AlreadyMigrated
+            );
+            LegacyStakingPool::deserialize(&mut &data[8..])?
+        };
+        require_keys_eq!(
+            legacy.authority,
+            ctx.accounts.authority.key(),
+            ErrorCode::Unauthorized
+        );
+        require_keys_eq!(
+            legacy.stake_vault,
+            ctx.accounts.stake_vault.key(),
+            ErrorCode::InvalidStakeVault
+        );
+        let legacy_tiers = [
+            (legacy.unbonding_period_7, legacy.reward_multiplier_7),
+            (legacy.unbonding_period_14, legacy.reward_multiplier_14),
+            (legacy.unbonding_period_30, legacy.reward_multiplier_30),
+        ];
+        require!(
+            legacy_tiers
+                .iter()
+                .all(|(lock_duration, _)| *lock_duration <= MAX_LOCK_DURATION),
+            ErrorCode::InvalidLockTier
+        );
+
+        grow_account(
+            &pool_info,
+            8 + StakingPool::INIT_SPACE,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+        )?;
+
+        let staking_pool = StakingPool {
+            authority: legacy.authority,
+            guardian: Pubkey::default(),
+            stake_mint: legacy.stake_mint,
+            stake_vault: legacy.stake_vault,
+            reward_vault: ctx.accounts.reward_vault.key(),
+            insurance_vault: ctx.accounts.insurance_vault.key(),
+            unbonding_period,
+            min_stake_amount: legacy.min_stake_amount,
+            max_total_staked: 0,
+            max_stake_per_user: 0,
+            early_unstake_max_penalty_bps: 0,
+            early_unstake_burn_bps: 0,
+            total_staked: legacy.total_staked,
+            // Legacy shares were worth exactly one token at migration time.
+            share_price: SHARE_PRICE_PRECISION,
+            total_stakers: 0,
+            total_weighted_stake: 0,
+            acc_reward_per_weight: 0,
+            reward_balance: 0,
+            total_rewards_paid: 0,
+            receipt_mint: Pubkey::default(),
+            liquid_reward_multiplier: 0,
+            liquid_shares: 0,
+            liquid_reward_debt: 0,
+            slash_max_bps: 0,
+            slash_cooldown: 0,
+            last_slash_time: 0,
+            state: if legacy.emergency_pause {
+                PoolState::Emergency
+            } else {
+                PoolState::Active
+            },
+            generation: 0,
+            bump: legacy.bump,
+        };
+        staking_pool.try_serialize(&mut &mut pool_info.try_borrow_mut_data()?[..])?;
+
+        let lock_tier_table = &mut ctx.accounts.lock_tier_table;
+        lock_tier_table.pool = pool_info.key();
+        lock_tier_table.tiers = legacy_tiers
+            .into_iter()
+            .map(|(lock_duration, reward_multiplier)| LockTier {
+                lock_duration,
+                reward_multiplier,
+                enabled: true,
+                capacity: 0,
+                staked_shares: 0,
+            })
+            .collect();
+        lock_tier_table.bump = ctx.bumps.lock_tier_table;
+
+        emit!(LegacyPoolMigrated {
+            pool: pool_info.key(),
+            total_staked: legacy.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Moves a position from the original one-per-user PDA to the next
+    /// indexed position of its owner, keeping its amount, lock and unbonding
+    /// state, and closes the legacy account. The pool must be migrated first.
+    pub fn migrate_legacy_position(ctx: Context<MigrateLegacyPosition>) -> Result<()> {
+        let legacy_info = ctx.accounts.legacy_position.to_account_info();
+        let legacy = {
+            let data = legacy_info.try_borrow_data()?;
+            require!(
+                data.len() == 8 + LegacyStakePosition::INIT_SPACE
+                    && data[..8] == StakePosition::DISCRIMINATOR,
+                ErrorCode::AlreadyMigrated
+            );
+            LegacyStakePosition::deserialize(&mut &data[8..])?
+        };
+        require_keys_eq!(legacy.user, ctx.accounts.user.key(), ErrorCode::NotPositionOwner);
+
+        let tier_index = legacy.lock_period as u8;
+        let tier = *ctx
+            .accounts
+            .lock_tier_table
+            .tiers
+            .get(tier_index as usize)
+            .ok_or(ErrorCode::InvalidLockTier)?;
+        let staking_pool = &ctx.accounts.staking_pool;
+        let user_stake_account = &mut ctx.accounts.user_stake_account;
+        let stake_position = &mut ctx.accounts.stake_position;
+        stake_position.user = legacy.user;
+        stake_position.pool = staking_pool.key();
+        stake_position.index = user_stake_account.position_count;
+        stake_position.shares = legacy.amount;
+        stake_position.tier_index = tier_index;
+        stake_position.lock_duration = tier.lock_duration;
+        stake_position.reward_multiplier = legacy.reward_multiplier;
+        stake_position.stake_timestamp = legacy.stake_timestamp;
+        stake_position.lock_end = legacy.unbonding_end;
+        stake_position.unbonding_end = if legacy.is_unbonding {
+            legacy.unbonding_end
+        } else {
+            0
+        };
+        stake_position.is_unbonding = legacy.is_unbonding;
+        stake_position.reward_debt = reward_debt(staking_pool, stake_position)?;
+        stake_position.pending_rewards = 0;
+        stake_position.position_mint = Pubkey::default();
+        stake_position.bump = ctx.bumps.stake_position;
+
+        user_stake_account.position_count += 1;
+        user_stake_account.active_positions += 1;
+        user_stake_account.staked_shares += stake_position.shares;
+        ctx.accounts.lock_tier_table.tiers[tier_index as usize].staked_shares +=
+            stake_position.shares;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        if !stake_position.is_unbonding {
+            staking_pool.total_weighted_stake += position_weight(stake_position);
+        }
+        if user_stake_account.active_positions == 1 {
+            staking_pool.total_stakers += 1;
+        }
+
+        // Close the legacy account; the runtime reclaims it once it is empty.
+        let user_info = ctx.accounts.user.to_account_info();
+        **user_info.try_borrow_mut_lamports()? += legacy_info.lamports();
+        **legacy_info.try_borrow_mut_lamports()? = 0;
+        legacy_info.assign(&system_program::ID);
+        legacy_info.realloc(0, false)?;
+
+        emit!(LegacyPositionMigrated {
+            user: legacy.user,
+            position: stake_position.key(),
+            amount: legacy.amount,
+            tier_index,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(
+    unbonding_period: u64,
+    min_stake_amount: u64,
+    early_unstake_max_penalty_bps: u16,
+    early_unstake_burn_bps: u16,
+    generation: u8
+)]
+pub struct InitializeStakingPool<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakingPool::INIT_SPACE,
+        seeds = [b"staking_pool", stake_mint.key().as_ref(), generation_seed(&generation)],
+        bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub stake_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stake_mint,
+        token::authority = staking_pool,
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stake_mint,
+        token::authority = staking_pool,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stake_mint,
+        token::authority = staking_pool,
+    )]
+    pub insurance_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + LockTierTable::INIT_SPACE,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStakingPool<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        has_one = authority
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Slash<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        has_one = authority,
+        has_one = stake_vault,
+        has_one = insurance_vault
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawInsuranceFund<'info> {
+    #[account(
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        has_one = authority,
+        has_one = insurance_vault
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut)]
+    pub insurance_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub destination: Account<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ManageLockTiers<'info> {
+    #[account(
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        has_one = authority
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump = lock_tier_table.bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUserStake<'info> {
+    #[account(
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + UserStakeAccount::INIT_SPACE,
+        seeds = [b"user_stake", user.key().as_ref(), staking_pool.key().as_ref()],
+        bump
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeTokens<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump = lock_tier_table.bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), staking_pool.key().as_ref()],
+        bump = user_stake_account.bump,
+        has_one = user
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + StakePosition::INIT_SPACE,
+        seeds = [
+            b"stake_position",
+            user.key().as_ref(),
+            staking_pool.key().as_ref(),
+            &user_stake_account.position_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    /// Only needed when tokenizing the position at creation.
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"position_mint", stake_position.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = staking_pool,
+    )]
+    pub position_mint: Option<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = user,
+        token::mint = position_mint,
+        token::authority = user,
+    )]
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct IncreaseStake<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump = lock_tier_table.bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"user_stake",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref()
+        ],
+        bump = user_stake_account.bump
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    pub user: Signer<'info>,
+
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct InitiateUnstake<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    pub user: Signer<'info>,
+
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct InitiatePartialUnstake<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), staking_pool.key().as_ref()],
+        bump = user_stake_account.bump,
+        has_one = user
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            user.key().as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump,
+        has_one = user
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 + StakePosition::INIT_SPACE,
+        seeds = [
+            b"stake_position",
+            user.key().as_ref(),
+            staking_pool.key().as_ref(),
+            &user_stake_account.position_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub unbonding_position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelUnstake<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    pub user: Signer<'info>,
+
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteUnstake<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump = lock_tier_table.bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"user_stake",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref()
+        ],
+        bump = user_stake_account.bump
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump,
+        close = user
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub position_mint: Option<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct EarlyUnstake<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut, address = staking_pool.stake_mint)]
+    pub stake_mint: Account<'info, Mint>,
+
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump = lock_tier_table.bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"user_stake",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref()
+        ],
+        bump = user_stake_account.bump
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump,
+        close = user
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub position_mint: Option<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump = lock_tier_table.bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"user_stake",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref()
+        ],
+        bump = user_stake_account.bump
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump,
+        close = user
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(mut)]
+    pub position_mint: Option<Account<'info, Mint>>,
+
+    #[account(mut)]
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FundStakingRewards<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    pub funder: Signer<'info>,
+
+    #[account(mut)]
+    pub funder_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SyncStakingRewards<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimStakingRewards<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    pub user: Signer<'info>,
+
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RelockPosition<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump = lock_tier_table.bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
 
-    pub fn toggle_emergency_pause(ctx: Context<ToggleEmergencyPause>, pause: bool) -> Result<()> {
-        let staking_pool = &mut ctx.accounts.staking_pool;
-        staking_pool.emergency_pause = pause;
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            stake_position.user.as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump
+    )]
+    pub stake_position: Account<'info, StakePosition>,
 
-        emit!(EmergencyPauseToggled {
-            pool: staking_pool.key(),
-            paused: pause,
-        });
+    pub user: Signer<'info>,
 
-        Ok(())
-    }
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
 }
 
 #[derive(Accounts)]
-pub struct InitializeStakingPool<'info> {
+pub struct MigratePosition<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            source_pool.stake_mint.as_ref(),
+            generation_seed(&source_pool.generation)
+        ],
+        bump = source_pool.bump
+    )]
+    pub source_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", source_pool.key().as_ref()],
+        bump = source_lock_tier_table.bump
+    )]
+    pub source_lock_tier_table: Box<Account<'info, LockTierTable>>,
+
+    #[account(mut, address = source_pool.stake_vault)]
+    pub source_stake_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, address = source_pool.reward_vault)]
+    pub source_reward_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), source_pool.key().as_ref()],
+        bump = source_user_stake_account.bump,
+        has_one = user
+    )]
+    pub source_user_stake_account: Box<Account<'info, UserStakeAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            user.key().as_ref(),
+            source_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump,
+        has_one = user,
+        close = user
+    )]
+    pub stake_position: Box<Account<'info, StakePosition>>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            destination_pool.stake_mint.as_ref(),
+            generation_seed(&destination_pool.generation)
+        ],
+        bump = destination_pool.bump,
+        constraint = destination_pool.key() != source_pool.key() @ ErrorCode::SamePool,
+        constraint = destination_pool.stake_mint == source_pool.stake_mint @ ErrorCode::MintMismatch
+    )]
+    pub destination_pool: Box<Account<'info, StakingPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", destination_pool.key().as_ref()],
+        bump = destination_lock_tier_table.bump
+    )]
+    pub destination_lock_tier_table: Box<Account<'info, LockTierTable>>,
+
+    #[account(mut, address = destination_pool.stake_vault)]
+    pub destination_stake_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, address = destination_pool.reward_vault)]
+    pub destination_reward_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), destination_pool.key().as_ref()],
+        bump = destination_user_stake_account.bump,
+        has_one = user
+    )]
+    pub destination_user_stake_account: Box<Account<'info, UserStakeAccount>>,
+
     #[account(
         init,
-        payer = authority,
-        space = 8 + StakingPool::INIT_SPACE,
-        seeds = [b"staking_pool", stake_mint.key().as_ref()],
+        payer = user,
+        space = 8 + StakePosition::INIT_SPACE,
+        seeds = [
+            b"stake_position",
+            user.key().as_ref(),
+            destination_pool.key().as_ref(),
+            &destination_user_stake_account.position_count.to_le_bytes()
+        ],
         bump
     )]
+    pub new_position: Box<Account<'info, StakePosition>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TokenizePosition<'info> {
+    #[account(
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
     pub staking_pool: Account<'info, StakingPool>,
 
+    #[account(
+        mut,
+        seeds = [
+            b"stake_position",
+            user.key().as_ref(),
+            staking_pool.key().as_ref(),
+            &stake_position.index.to_le_bytes()
+        ],
+        bump = stake_position.bump,
+        has_one = user,
+        constraint = stake_position.position_mint == Pubkey::default() @ ErrorCode::PositionTokenized
+    )]
+    pub stake_position: Account<'info, StakePosition>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"position_mint", stake_position.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = staking_pool,
+    )]
+    pub position_mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = user,
+        token::mint = position_mint,
+        token::authority = user,
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub user: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct EnableLiquidStaking<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        has_one = authority,
+        has_one = stake_mint
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
 
     pub stake_mint: Account<'info, Mint>,
 
     #[account(
         init,
         payer = authority,
-        token::mint = stake_mint,
-        token::authority = staking_pool,
+        seeds = [b"receipt_mint", staking_pool.key().as_ref()],
+        bump,
+        mint::decimals = stake_mint.decimals,
+        mint::authority = staking_pool,
     )]
-    pub stake_vault: Account<'info, TokenAccount>,
+    pub receipt_mint: Account<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
 
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
@@ -237,22 +2769,88 @@ pub struct InitializeStakingPool<'info> {
 }
 
 #[derive(Accounts)]
-pub struct StakeTokens<'info> {
+pub struct LiquidStake<'info> {
     #[account(
         mut,
-        seeds = [b"staking_pool", staking_pool.stake_mint.as_ref()],
-        bump = staking_pool.bump
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        has_one = receipt_mint @ ErrorCode::LiquidStakingDisabled
     )]
     pub staking_pool: Account<'info, StakingPool>,
 
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub receipt_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), staking_pool.key().as_ref()],
+        bump = user_stake_account.bump,
+        has_one = user
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
+    pub user: Signer<'info>,
+
     #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut, token::mint = receipt_mint)]
+    pub user_receipt_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidUnstake<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        has_one = receipt_mint @ ErrorCode::LiquidStakingDisabled
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(mut, address = staking_pool.stake_vault)]
     pub stake_vault: Account<'info, TokenAccount>,
 
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub receipt_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake", user.key().as_ref(), staking_pool.key().as_ref()],
+        bump = user_stake_account.bump,
+        has_one = user
+    )]
+    pub user_stake_account: Account<'info, UserStakeAccount>,
+
     #[account(
         init,
         payer = user,
         space = 8 + StakePosition::INIT_SPACE,
-        seeds = [b"stake_position", user.key().as_ref(), staking_pool.key().as_ref()],
+        seeds = [
+            b"stake_position",
+            user.key().as_ref(),
+            staking_pool.key().as_ref(),
+            &user_stake_account.position_count.to_le_bytes()
+        ],
         bump
     )]
     pub stake_position: Account<'info, StakePosition>,
@@ -260,109 +2858,298 @@ pub struct StakeTokens<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = receipt_mint)]
+    pub user_receipt_account: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct InitiateUnstake<'info> {
+pub struct EmergencyLiquidWithdraw<'info> {
     #[account(
-        seeds = [b"staking_pool", staking_pool.stake_mint.as_ref()],
-        bump = staking_pool.bump
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        has_one = receipt_mint @ ErrorCode::LiquidStakingDisabled
     )]
     pub staking_pool: Account<'info, StakingPool>,
 
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub receipt_mint: Account<'info, Mint>,
+
     #[account(
         mut,
-        seeds = [b"stake_position", user.key().as_ref(), staking_pool.key().as_ref()],
-        bump = stake_position.bump,
+        seeds = [b"user_stake", user.key().as_ref(), staking_pool.key().as_ref()],
+        bump = user_stake_account.bump,
         has_one = user
     )]
-    pub stake_position: Account<'info, StakePosition>,
+    pub user_stake_account: Option<Account<'info, UserStakeAccount>>,
 
     pub user: Signer<'info>,
+
+    #[account(mut, token::mint = receipt_mint)]
+    pub user_receipt_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct CompleteUnstake<'info> {
+pub struct CompoundLiquidRewards<'info> {
     #[account(
         mut,
-        seeds = [b"staking_pool", staking_pool.stake_mint.as_ref()],
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
         bump = staking_pool.bump
     )]
     pub staking_pool: Account<'info, StakingPool>,
 
-    #[account(mut)]
+    #[account(mut, address = staking_pool.stake_vault)]
     pub stake_vault: Account<'info, TokenAccount>,
 
+    #[account(mut, address = staking_pool.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetPoolState<'info> {
     #[account(
         mut,
-        seeds = [b"stake_position", user.key().as_ref(), staking_pool.key().as_ref()],
-        bump = stake_position.bump,
-        has_one = user,
-        close = user
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump,
+        constraint = authority.key() == staking_pool.authority
+            || authority.key() == staking_pool.guardian @ ErrorCode::Unauthorized
     )]
-    pub stake_position: Account<'info, StakePosition>,
+    pub staking_pool: Account<'info, StakingPool>,
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+    /// The pool authority or its guardian.
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLegacyPool<'info> {
+    /// CHECK: Still in the legacy layout; validated and rewritten by the
+    /// instruction.
+    #[account(
+        mut,
+        seeds = [b"staking_pool", stake_mint.key().as_ref()],
+        bump
+    )]
+    pub staking_pool: UncheckedAccount<'info>,
 
     #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub authority: Signer<'info>,
+
+    pub stake_mint: Account<'info, Mint>,
+
+    pub stake_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stake_mint,
+        token::authority = staking_pool,
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stake_mint,
+        token::authority = staking_pool,
+    )]
+    pub insurance_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + LockTierTable::INIT_SPACE,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
 
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
-#[derive(Accounts)]
-pub struct EmergencyWithdraw<'info> {
+#[derive(Accounts)]
+pub struct MigrateLegacyPosition<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"staking_pool",
+            staking_pool.stake_mint.as_ref(),
+            generation_seed(&staking_pool.generation)
+        ],
+        bump = staking_pool.bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"lock_tiers", staking_pool.key().as_ref()],
+        bump = lock_tier_table.bump
+    )]
+    pub lock_tier_table: Account<'info, LockTierTable>,
+
+    /// CHECK: Still in the legacy layout; validated and closed by the
+    /// instruction.
+    #[account(
+        mut,
+        seeds = [b"stake_position", user.key().as_ref(), staking_pool.key().as_ref()],
+        bump
+    )]
+    pub legacy_position: UncheckedAccount<'info>,
+
     #[account(
         mut,
-        seeds = [b"staking_pool", staking_pool.stake_mint.as_ref()],
-        bump = staking_pool.bump
+        seeds = [b"user_stake", user.key().as_ref(), staking_pool.key().as_ref()],
+        bump = user_stake_account.bump,
+        has_one = user
     )]
-    pub staking_pool: Account<'info, StakingPool>,
-
-    #[account(mut)]
-    pub stake_vault: Account<'info, TokenAccount>,
+    pub user_stake_account: Account<'info, UserStakeAccount>,
 
     #[account(
-        mut,
-        seeds = [b"stake_position", user.key().as_ref(), staking_pool.key().as_ref()],
-        bump = stake_position.bump,
-        has_one = user,
-        close = user
+        init,
+        payer = user,
+        space = 8 + StakePosition::INIT_SPACE,
+        seeds = [
+            b"stake_position",
+            user.key().as_ref(),
+            staking_pool.key().as_ref(),
+            &user_stake_account.position_count.to_le_bytes()
+        ],
+        bump
     )]
     pub stake_position: Account<'info, StakePosition>,
 
     #[account(mut)]
     pub user: Signer<'info>,
 
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
 
-    pub token_program: Program<'info, Token>,
+#[account]
+#[derive(InitSpace)]
+pub struct StakingPool {
+    pub authority: Pubkey,
+    pub guardian: Pubkey,
+    pub stake_mint: Pubkey,
+    pub stake_vault: Pubkey,
+    pub reward_vault: Pubkey,
+    pub insurance_vault: Pubkey,
+    pub unbonding_period: u64,
+    pub min_stake_amount: u64,
+    pub max_total_staked: u64,
+    pub max_stake_per_user: u64,
+    pub early_unstake_max_penalty_bps: u16,
+    pub early_unstake_burn_bps: u16,
+    pub total_staked: u64,
+    pub share_price: u128,
+    pub total_stakers: u64,
+    pub total_weighted_stake: u128,
+    pub acc_reward_per_weight: u128,
+    pub reward_balance: u64,
+    pub total_rewards_paid: u64,
+    pub receipt_mint: Pubkey,
+    pub liquid_reward_multiplier: u64,
+    pub liquid_shares: u64,
+    pub liquid_reward_debt: u128,
+    pub slash_max_bps: u16,
+    pub slash_cooldown: u64,
+    pub last_slash_time: i64,
+    pub state: PoolState,
+    pub generation: u8,
+    pub bump: u8,
 }
 
-#[derive(Accounts)]
-pub struct ToggleEmergencyPause<'info> {
-    #[account(
-        mut,
-        seeds = [b"staking_pool", staking_pool.stake_mint.as_ref()],
-        bump = staking_pool.bump,
-        has_one = authority
-    )]
-    pub staking_pool: Account<'info, StakingPool>,
+#[account]
+#[derive(InitSpace)]
+pub struct UserStakeAccount {
+    pub user: Pubkey,
+    pub pool: Pubkey,
+    pub position_count: u64,
+    pub active_positions: u64,
+    pub staked_shares: u64,
+    /// Liquid shares this user staked and has not yet unstaked, counted
+    /// against `max_stake_per_user`.
+    pub liquid_shares: u64,
+    pub bump: u8,
+}
 
-    pub authority: Signer<'info>,
+#[account]
+#[derive(InitSpace)]
+pub struct StakePosition {
+    pub user: Pubkey,
+    pub pool: Pubkey,
+    pub index: u64,
+    pub shares: u64,
+    pub tier_index: u8,
+    pub lock_duration: u64,
+    pub reward_multiplier: u64,
+    pub stake_timestamp: i64,
+    pub lock_end: i64,
+    pub unbonding_end: i64,
+    pub is_unbonding: bool,
+    pub reward_debt: u128,
+    pub pending_rewards: u64,
+    pub position_mint: Pubkey,
+    pub bump: u8,
 }
 
 #[account]
 #[derive(InitSpace)]
-pub struct StakingPool {
+pub struct LockTierTable {
+    pub pool: Pubkey,
+    #[max_len(MAX_LOCK_TIERS)]
+    pub tiers: Vec<LockTier>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum PoolState {
+    Active,
+    Paused,
+    Emergency,
+    Sunset,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct LockTier {
+    pub lock_duration: u64,
+    pub reward_multiplier: u64,
+    pub enabled: bool,
+    pub capacity: u64,
+    pub staked_shares: u64,
+}
+
+/// `StakingPool` as written by the original fixed-lock program.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct LegacyStakingPool {
     pub authority: Pubkey,
     pub stake_mint: Pubkey,
     pub stake_vault: Pubkey,
@@ -379,13 +3166,14 @@ pub struct StakingPool {
     pub bump: u8,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct StakePosition {
+/// `StakePosition` as written by the original fixed-lock program, one per
+/// user and pool.
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct LegacyStakePosition {
     pub user: Pubkey,
     pub pool: Pubkey,
     pub amount: u64,
-    pub lock_period: LockPeriod,
+    pub lock_period: LegacyLockPeriod,
     pub reward_multiplier: u64,
     pub stake_timestamp: i64,
     pub unbonding_end: i64,
@@ -393,8 +3181,8 @@ pub struct StakePosition {
     pub bump: u8,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
-pub enum LockPeriod {
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub enum LegacyLockPeriod {
     Days7,
     Days14,
     Days30,
@@ -407,25 +3195,126 @@ pub struct StakingPoolInitialized {
     pub min_stake_amount: u64,
 }
 
+#[event]
+pub struct LegacyPoolMigrated {
+    pub pool: Pubkey,
+    pub total_staked: u64,
+}
+
+#[event]
+pub struct LegacyPositionMigrated {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub amount: u64,
+    pub tier_index: u8,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub pool: Pubkey,
+    pub guardian: Pubkey,
+}
+
+#[event]
+pub struct SlashConfigUpdated {
+    pub pool: Pubkey,
+    pub max_bps: u16,
+    pub cooldown: u64,
+}
+
+#[event]
+pub struct Slashed {
+    pub pool: Pubkey,
+    pub bps: u16,
+    pub amount: u64,
+    pub share_price: u128,
+    pub reason_code: u16,
+}
+
+#[event]
+pub struct InsuranceFundWithdrawn {
+    pub pool: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct LockTierUpdated {
+    pub pool: Pubkey,
+    pub tier_index: u8,
+    pub lock_duration: u64,
+    pub reward_multiplier: u64,
+    pub enabled: bool,
+    pub capacity: u64,
+}
+
+#[event]
+pub struct StakeLimitsUpdated {
+    pub pool: Pubkey,
+    pub min_stake_amount: u64,
+    pub max_total_staked: u64,
+    pub max_stake_per_user: u64,
+}
+
 #[event]
 pub struct TokensStaked {
     pub user: Pubkey,
+    pub position: Pubkey,
+    pub index: u64,
     pub amount: u64,
-    pub lock_period: LockPeriod,
-    pub unbonding_end: i64,
+    pub tier_index: u8,
+    pub lock_end: i64,
+}
+
+#[event]
+pub struct StakeIncreased {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub amount: u64,
+    pub new_amount: u64,
+    pub lock_end: i64,
 }
 
 #[event]
 pub struct UnstakeInitiated {
     pub user: Pubkey,
+    pub position: Pubkey,
     pub amount: u64,
+    pub remaining_staked: u64,
     pub unbonding_end: i64,
 }
 
+#[event]
+pub struct UnstakeCancelled {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub amount: u64,
+    pub total_weighted_stake: u128,
+}
+
 #[event]
 pub struct UnstakeCompleted {
     pub user: Pubkey,
     pub amount: u64,
+    pub rewards: u64,
+}
+
+#[event]
+pub struct EarlyUnstakeConfigUpdated {
+    pub pool: Pubkey,
+    pub max_penalty_bps: u16,
+    pub burn_bps: u16,
+}
+
+#[event]
+pub struct EarlyUnstaked {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub amount: u64,
+    pub penalty: u64,
+    pub burned: u64,
+    pub redistributed: u64,
+    pub rewards: u64,
 }
 
 #[event]
@@ -435,17 +3324,101 @@ pub struct EmergencyWithdrawal {
 }
 
 #[event]
-pub struct EmergencyPauseToggled {
+pub struct StakingRewardsFunded {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub distributed: u64,
+}
+
+#[event]
+pub struct StakingRewardsSynced {
+    pub pool: Pubkey,
+    pub distributed: u64,
+    pub acc_reward_per_weight: u128,
+}
+
+#[event]
+pub struct StakingRewardsClaimed {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PositionRelocked {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub tier_index: u8,
+    pub lock_end: i64,
+}
+
+#[event]
+pub struct PositionMigrated {
+    pub user: Pubkey,
+    pub source_position: Pubkey,
+    pub new_position: Pubkey,
+    pub destination_pool: Pubkey,
+    pub amount: u64,
+    pub rewards: u64,
+    pub lock_end: i64,
+}
+
+#[event]
+pub struct PositionTokenized {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub position_mint: Pubkey,
+}
+
+#[event]
+pub struct LiquidStakingEnabled {
+    pub pool: Pubkey,
+    pub receipt_mint: Pubkey,
+    pub reward_multiplier: u64,
+}
+
+#[event]
+pub struct LiquidStaked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub receipt_amount: u64,
+    pub liquid_total_assets: u64,
+}
+
+#[event]
+pub struct LiquidUnstaked {
+    pub user: Pubkey,
+    pub position: Pubkey,
+    pub receipt_amount: u64,
+    pub amount: u64,
+    pub unbonding_end: i64,
+}
+
+#[event]
+pub struct LiquidRewardsCompounded {
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub liquid_total_assets: u64,
+}
+
+#[event]
+pub struct PoolStateChanged {
     pub pool: Pubkey,
-    pub paused: bool,
+    pub previous_state: PoolState,
+    pub state: PoolState,
 }
 
 #[error_code]
 pub enum ErrorCode {
-    #[msg("Staking pool is paused")]
-    PoolPaused,
-    #[msg("Staking pool is not paused")]
-    PoolNotPaused,
+    #[msg("Staking pool is not accepting stakes")]
+    PoolNotActive,
+    #[msg("Staking pool is in emergency mode")]
+    PoolInEmergency,
+    #[msg("Staking pool is not in emergency mode")]
+    PoolNotInEmergency,
+    #[msg("Staking pool has been sunset")]
+    PoolSunset,
     #[msg("Amount below minimum stake")]
     BelowMinStake,
     #[msg("Already unbonding")]
@@ -454,4 +3427,399 @@ pub enum ErrorCode {
     NotUnbonding,
     #[msg("Unbonding period not complete")]
     UnbondingPeriodNotComplete,
+    #[msg("Lock period not complete")]
+    LockPeriodNotComplete,
+    #[msg("Lock period already complete")]
+    LockPeriodComplete,
+    #[msg("Basis points must not exceed 10000")]
+    InvalidBasisPoints,
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Invalid lock tier")]
+    InvalidLockTier,
+    #[msg("Unbonding period is too long")]
+    InvalidUnbondingPeriod,
+    #[msg("Lock tier is disabled")]
+    LockTierDisabled,
+    #[msg("Lock tier table is full")]
+    TooManyLockTiers,
+    #[msg("No rewards to claim")]
+    NoRewardsToClaim,
+    #[msg("Arithmetic overflow in calculation")]
+    ArithmeticOverflow,
+    #[msg("Liquid staking is not enabled for this pool")]
+    LiquidStakingDisabled,
+    #[msg("Signer does not own this position")]
+    NotPositionOwner,
+    #[msg("Position is represented by a token")]
+    PositionTokenized,
+    #[msg("Tokenizing requires the position mint and token account")]
+    MissingPositionMint,
+    #[msg("Slash exceeds the per-event cap")]
+    SlashExceedsCap,
+    #[msg("Slash cooldown has not elapsed")]
+    SlashCooldownActive,
+    #[msg("Stake would exceed the pool cap")]
+    PoolCapExceeded,
+    #[msg("Stake would exceed the per-user limit")]
+    UserStakeLimitExceeded,
+    #[msg("Stake would exceed the lock tier capacity")]
+    LockTierCapacityExceeded,
+    #[msg("New lock would end earlier or be shorter than the current one")]
+    LockShortened,
+    #[msg("Source and destination pools are the same")]
+    SamePool,
+    #[msg("Pools do not share a stake mint")]
+    MintMismatch,
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+    #[msg("Account is not in the legacy layout")]
+    AlreadyMigrated,
+    #[msg("Stake vault does not match the pool")]
+    InvalidStakeVault,
+}
+
+/// Reallocs a program-owned account to `space`, topping its rent up from
+/// `payer`.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    space: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let rent_due = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if rent_due > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        let cpi_ctx = CpiContext::new(system_program.to_account_info(), cpi_accounts);
+
+        system_program::transfer(cpi_ctx, rent_due)?;
+    }
+
+    account.realloc(space, true)?;
+
+    Ok(())
+}
+
+/// Extra PDA seed of a staking pool. Empty for generation 0, so the first
+/// pool of a mint keeps the address it had before successor pools existed.
+fn generation_seed(generation: &u8) -> &[u8] {
+    if *generation == 0 {
+        &[]
+    } else {
+        std::slice::from_ref(generation)
+    }
+}
+
+/// The unix timestamp `seconds` after `now`.
+fn add_seconds(now: i64, seconds: u64) -> Result<i64> {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| now.checked_add(seconds))
+        .ok_or_else(|| error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Reward weight of a position: staked amount times its lock multiplier.
+fn position_weight(position: &StakePosition) -> u128 {
+    position.shares as u128 * position.reward_multiplier as u128
+}
+
+fn reward_debt(pool: &StakingPool, position: &StakePosition) -> Result<u128> {
+    if position.is_unbonding {
+        return Ok(0);
+    }
+
+    Ok(position_weight(position)
+        .checked_mul(pool.acc_reward_per_weight)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / REWARD_PRECISION)
+}
+
+/// Moves rewards accrued since the last settlement into `pending_rewards`.
+/// Callers reset `reward_debt` after changing the position's weight.
+fn settle_rewards(pool: &StakingPool, position: &mut StakePosition) -> Result<()> {
+    let accrued = reward_debt(pool, position)?.saturating_sub(position.reward_debt);
+    position.pending_rewards = position
+        .pending_rewards
+        .checked_add(u64::try_from(accrued).map_err(|_| error!(ErrorCode::ArithmeticOverflow))?)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+
+    Ok(())
+}
+
+/// Settles the position's rewards and moves it into the unbonding cooldown,
+/// removing its weight so it stops accruing.
+fn begin_unbonding(pool: &mut StakingPool, position: &mut StakePosition, now: i64) -> Result<()> {
+    settle_rewards(pool, position)?;
+    pool.total_weighted_stake -= position_weight(position);
+
+    position.is_unbonding = true;
+    position.unbonding_end = add_seconds(now, pool.unbonding_period)?;
+    position.reward_debt = 0;
+
+    Ok(())
+}
+
+/// Spreads reward vault tokens that are not yet accounted for across the
+/// weighted stake. Returns the amount distributed, which is zero while
+/// nothing is staked so the tokens wait for the next sync.
+fn sync_rewards(pool: &mut StakingPool, vault_balance: u64) -> Result<u64> {
+    let undistributed = vault_balance.saturating_sub(pool.reward_balance);
+    distribute_rewards(pool, undistributed)
+}
+
+/// Credits `amount` reward vault tokens to the accumulator. Returns zero and
+/// leaves the tokens unaccounted while nothing is staked.
+fn distribute_rewards(pool: &mut StakingPool, amount: u64) -> Result<u64> {
+    if amount == 0 || pool.total_weighted_stake == 0 {
+        return Ok(0);
+    }
+
+    let increment = (amount as u128)
+        .checked_mul(REWARD_PRECISION)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / pool.total_weighted_stake;
+    pool.acc_reward_per_weight = pool
+        .acc_reward_per_weight
+        .checked_add(increment)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    pool.reward_balance += amount;
+
+    Ok(amount)
+}
+
+/// Linear penalty: the maximum penalty scaled by the share of the lock that
+/// is still remaining at `now`.
+fn early_unstake_penalty(
+    pool: &StakingPool,
+    position: &StakePosition,
+    amount: u64,
+    now: i64,
+) -> Result<u64> {
+    if position.lock_duration == 0 {
+        return Ok(0);
+    }
+
+    let remaining = (position.lock_end - now).clamp(0, position.lock_duration as i64) as u128;
+    let penalty = (amount as u128)
+        .checked_mul(pool.early_unstake_max_penalty_bps as u128)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        .checked_mul(remaining)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / position.lock_duration as u128
+        / 10000;
+
+    Ok(penalty as u64)
+}
+
+fn liquid_reward_debt(pool: &StakingPool) -> Result<u128> {
+    Ok((pool.liquid_shares as u128 * pool.liquid_reward_multiplier as u128)
+        .checked_mul(pool.acc_reward_per_weight)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / REWARD_PRECISION)
+}
+
+/// Folds rewards accrued by the liquid share into `liquid_shares`. Returns the
+/// amount the caller must move from the reward vault into the stake vault.
+fn accrue_liquid_rewards(pool: &mut StakingPool) -> Result<u64> {
+    let accrued = liquid_reward_debt(pool)?.saturating_sub(pool.liquid_reward_debt);
+    let accrued = u64::try_from(accrued).map_err(|_| error!(ErrorCode::ArithmeticOverflow))?;
+    let shares = amount_to_shares(pool, accrued)?;
+
+    pool.liquid_shares += shares;
+    pool.total_staked += accrued;
+    pool.reward_balance -= accrued;
+    pool.total_rewards_paid += accrued;
+    pool.total_weighted_stake += shares as u128 * pool.liquid_reward_multiplier as u128;
+    pool.liquid_reward_debt = liquid_reward_debt(pool)?;
+
+    Ok(accrued)
+}
+
+fn receipt_for_amount(pool: &StakingPool, receipt_supply: u64, amount: u64) -> Result<u64> {
+    let liquid_assets = shares_to_amount(pool, pool.liquid_shares)?;
+    let receipt_amount = (amount as u128)
+        .checked_mul(receipt_supply as u128 + LIQUID_VIRTUAL_OFFSET)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / (liquid_assets as u128 + LIQUID_VIRTUAL_OFFSET);
+
+    u64::try_from(receipt_amount).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+fn amount_for_receipt(pool: &StakingPool, receipt_supply: u64, receipt_amount: u64) -> Result<u64> {
+    let liquid_assets = shares_to_amount(pool, pool.liquid_shares)?;
+    let amount = (receipt_amount as u128)
+        .checked_mul(liquid_assets as u128 + LIQUID_VIRTUAL_OFFSET)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / (receipt_supply as u128 + LIQUID_VIRTUAL_OFFSET);
+
+    u64::try_from(amount).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Token value of `shares` at the pool's current share price. Slashing lowers
+/// the price, so every position loses the same fraction of its value while
+/// its reward weight stays unchanged.
+fn shares_to_amount(pool: &StakingPool, shares: u64) -> Result<u64> {
+    let amount = (shares as u128)
+        .checked_mul(pool.share_price)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / SHARE_PRICE_PRECISION;
+
+    u64::try_from(amount).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+fn amount_to_shares(pool: &StakingPool, amount: u64) -> Result<u64> {
+    let shares = (amount as u128)
+        .checked_mul(SHARE_PRICE_PRECISION)
+        .ok_or(ErrorCode::ArithmeticOverflow)?
+        / pool.share_price;
+
+    u64::try_from(shares).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Untokenized positions belong to the staker who opened them; tokenized
+/// positions belong to whoever holds the position token.
+fn check_position_owner(
+    position: &StakePosition,
+    owner: Pubkey,
+    position_token_account: &Option<Account<TokenAccount>>,
+) -> Result<()> {
+    if position.position_mint == Pubkey::default() {
+        require_keys_eq!(position.user, owner, ErrorCode::NotPositionOwner);
+        return Ok(());
+    }
+
+    let token_account = position_token_account
+        .as_ref()
+        .ok_or(ErrorCode::NotPositionOwner)?;
+    require!(
+        token_account.mint == position.position_mint
+            && token_account.owner == owner
+            && token_account.amount == 1,
+        ErrorCode::NotPositionOwner
+    );
+
+    Ok(())
+}
+
+/// Mints the position's 1-of-1 token and drops the mint authority so the
+/// supply stays fixed at one.
+fn mint_position_token<'info>(
+    staking_pool: &Account<'info, StakingPool>,
+    position_mint: &Account<'info, Mint>,
+    position_token_account: &Account<'info, TokenAccount>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let seeds = &[
+        b"staking_pool",
+        staking_pool.stake_mint.as_ref(),
+        generation_seed(&staking_pool.generation),
+        &[staking_pool.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = MintTo {
+        mint: position_mint.to_account_info(),
+        to: position_token_account.to_account_info(),
+        authority: staking_pool.to_account_info(),
+    };
+    let cpi_ctx =
+        CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer);
+
+    token::mint_to(cpi_ctx, 1)?;
+
+    let cpi_accounts = SetAuthority {
+        account_or_mint: position_mint.to_account_info(),
+        current_authority: staking_pool.to_account_info(),
+    };
+    let cpi_ctx =
+        CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer);
+
+    token::set_authority(cpi_ctx, AuthorityType::MintTokens, None)
+}
+
+fn burn_position_token<'info>(
+    position: &StakePosition,
+    position_mint: &Option<Account<'info, Mint>>,
+    position_token_account: &Option<Account<'info, TokenAccount>>,
+    owner: &Signer<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    if position.position_mint == Pubkey::default() {
+        return Ok(());
+    }
+
+    let mint = position_mint.as_ref().ok_or(ErrorCode::NotPositionOwner)?;
+    let token_account = position_token_account
+        .as_ref()
+        .ok_or(ErrorCode::NotPositionOwner)?;
+    require_keys_eq!(mint.key(), position.position_mint, ErrorCode::NotPositionOwner);
+
+    let cpi_accounts = Burn {
+        mint: mint.to_account_info(),
+        from: token_account.to_account_info(),
+        authority: owner.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+
+    token::burn(cpi_ctx, 1)
+}
+
+/// Checks a deposit of `amount` against the pool cap and, when given, the
+/// user's limit and the tier's capacity. Zero limits are uncapped.
+fn check_stake_limits(
+    pool: &StakingPool,
+    user_stake_account: Option<&UserStakeAccount>,
+    tier: Option<&LockTier>,
+    amount: u64,
+) -> Result<()> {
+    if pool.max_total_staked > 0 {
+        require!(
+            pool.total_staked as u128 + amount as u128 <= pool.max_total_staked as u128,
+            ErrorCode::PoolCapExceeded
+        );
+    }
+
+    if let Some(user_stake_account) = user_stake_account {
+        if pool.max_stake_per_user > 0 {
+            let staked = shares_to_amount(
+                pool,
+                user_stake_account.staked_shares + user_stake_account.liquid_shares,
+            )?;
+            require!(
+                staked as u128 + amount as u128 <= pool.max_stake_per_user as u128,
+                ErrorCode::UserStakeLimitExceeded
+            );
+        }
+    }
+
+    if let Some(tier) = tier {
+        if tier.capacity > 0 {
+            let staked = shares_to_amount(pool, tier.staked_shares)?;
+            require!(
+                staked as u128 + amount as u128 <= tier.capacity as u128,
+                ErrorCode::LockTierCapacityExceeded
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes a closed position from its owner's and its tier's totals.
+fn release_position_shares(
+    lock_tier_table: &mut LockTierTable,
+    user_stake_account: &mut UserStakeAccount,
+    position: &StakePosition,
+) {
+    user_stake_account.staked_shares = user_stake_account
+        .staked_shares
+        .saturating_sub(position.shares);
+    if let Some(tier) = lock_tier_table.tiers.get_mut(position.tier_index as usize) {
+        tier.staked_shares = tier.staked_shares.saturating_sub(position.shares);
+    }
 }
@@ -0,0 +1,617 @@
+use anchor_lang::{prelude::Pubkey, system_program, InstructionData, ToAccountMetas};
+use anchor_spl::token::spl_token;
+use program_test_utils::{
+    account_exists, assert_error, create_mint, create_token_account, fetch_account, funded_keypair,
+    mint_to, program_test, send, set_unix_timestamp,
+    solana_program_test::ProgramTestContext,
+    solana_sdk::{
+        instruction::Instruction,
+        signature::{Keypair, Signer},
+        sysvar,
+    },
+    token_balance,
+};
+use staking_program::{accounts, instruction, ErrorCode, PoolState, StakePosition};
+
+const LOCK_DURATION: u64 = 7 * 86_400;
+const UNBONDING_PERIOD: u64 = 3 * 86_400;
+const STAKE_AMOUNT: u64 = 1_000_000;
+const EARLY_UNSTAKE_MAX_PENALTY_BPS: u16 = 5_000;
+
+struct Setup {
+    ctx: ProgramTestContext,
+    user: Keypair,
+    user_token_account: Pubkey,
+    stake_mint: Pubkey,
+    staking_pool: Pubkey,
+    stake_vault: Pubkey,
+    reward_vault: Pubkey,
+    lock_tier_table: Pubkey,
+    user_stake_account: Pubkey,
+    stake_position: Pubkey,
+}
+
+async fn stake_position(ctx: &mut ProgramTestContext, address: Pubkey) -> StakePosition {
+    fetch_account(ctx, address).await
+}
+
+fn stake_position_address(user: &Pubkey, staking_pool: &Pubkey, index: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"stake_position",
+            user.as_ref(),
+            staking_pool.as_ref(),
+            &index.to_le_bytes(),
+        ],
+        &staking_program::ID,
+    )
+    .0
+}
+
+/// Creates a pool with one `LOCK_DURATION` tier and a user holding a single
+/// `STAKE_AMOUNT` position in it.
+async fn setup() -> Setup {
+    let mut ctx = program_test!(staking_program).start_with_context().await;
+
+    let user = funded_keypair(&mut ctx).await;
+    let stake_mint = create_mint(&mut ctx, 6).await;
+    let user_token_account = create_token_account(&mut ctx, &stake_mint, &user.pubkey()).await;
+    mint_to(&mut ctx, &stake_mint, &user_token_account, STAKE_AMOUNT).await;
+    let stake_vault = Keypair::new();
+    let reward_vault = Keypair::new();
+    let insurance_vault = Keypair::new();
+    let payer = ctx.payer.pubkey();
+
+    let (staking_pool, _) = Pubkey::find_program_address(
+        &[b"staking_pool", stake_mint.as_ref()],
+        &staking_program::ID,
+    );
+    let (lock_tier_table, _) = Pubkey::find_program_address(
+        &[b"lock_tiers", staking_pool.as_ref()],
+        &staking_program::ID,
+    );
+    let (user_stake_account, _) = Pubkey::find_program_address(
+        &[b"user_stake", user.pubkey().as_ref(), staking_pool.as_ref()],
+        &staking_program::ID,
+    );
+    let stake_position = stake_position_address(&user.pubkey(), &staking_pool, 0);
+
+    let initialize = Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::InitializeStakingPool {
+            staking_pool,
+            authority: payer,
+            stake_mint,
+            stake_vault: stake_vault.pubkey(),
+            reward_vault: reward_vault.pubkey(),
+            insurance_vault: insurance_vault.pubkey(),
+            lock_tier_table,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeStakingPool {
+            unbonding_period: UNBONDING_PERIOD,
+            min_stake_amount: 1,
+            early_unstake_max_penalty_bps: EARLY_UNSTAKE_MAX_PENALTY_BPS,
+            early_unstake_burn_bps: 0,
+            generation: 0,
+        }
+        .data(),
+    };
+    let add_tier = Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::ManageLockTiers {
+            staking_pool,
+            lock_tier_table,
+            authority: payer,
+        }
+        .to_account_metas(None),
+        data: instruction::AddLockTier {
+            lock_duration: LOCK_DURATION,
+            reward_multiplier: 10_000,
+        }
+        .data(),
+    };
+    send(
+        &mut ctx,
+        &[initialize, add_tier],
+        &[&stake_vault, &reward_vault, &insurance_vault],
+    )
+    .await
+    .unwrap();
+
+    let initialize_user_stake = Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::InitializeUserStake {
+            staking_pool,
+            user_stake_account,
+            user: user.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitializeUserStake {}.data(),
+    };
+    let stake = Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::StakeTokens {
+            staking_pool,
+            lock_tier_table,
+            stake_vault: stake_vault.pubkey(),
+            user_stake_account,
+            stake_position,
+            user: user.pubkey(),
+            user_token_account,
+            position_mint: None,
+            position_token_account: None,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::StakeTokens {
+            amount: STAKE_AMOUNT,
+            tier_index: 0,
+            tokenize: false,
+        }
+        .data(),
+    };
+    send(&mut ctx, &[initialize_user_stake, stake], &[&user])
+        .await
+        .unwrap();
+
+    Setup {
+        ctx,
+        user,
+        user_token_account,
+        stake_mint,
+        staking_pool,
+        stake_vault: stake_vault.pubkey(),
+        reward_vault: reward_vault.pubkey(),
+        lock_tier_table,
+        user_stake_account,
+        stake_position,
+    }
+}
+
+fn initiate_unstake(setup: &Setup) -> Instruction {
+    Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::InitiateUnstake {
+            staking_pool: setup.staking_pool,
+            stake_position: setup.stake_position,
+            user: setup.user.pubkey(),
+            position_token_account: None,
+        }
+        .to_account_metas(None),
+        data: instruction::InitiateUnstake {}.data(),
+    }
+}
+
+fn complete_unstake(setup: &Setup, stake_position: Pubkey) -> Instruction {
+    Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::CompleteUnstake {
+            staking_pool: setup.staking_pool,
+            stake_vault: setup.stake_vault,
+            reward_vault: setup.reward_vault,
+            lock_tier_table: setup.lock_tier_table,
+            user_stake_account: setup.user_stake_account,
+            stake_position,
+            user: setup.user.pubkey(),
+            position_mint: None,
+            position_token_account: None,
+            user_token_account: setup.user_token_account,
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::CompleteUnstake {}.data(),
+    }
+}
+
+fn initiate_partial_unstake(setup: &Setup, unbonding_position: Pubkey, amount: u64) -> Instruction {
+    Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::InitiatePartialUnstake {
+            staking_pool: setup.staking_pool,
+            user_stake_account: setup.user_stake_account,
+            stake_position: setup.stake_position,
+            unbonding_position,
+            user: setup.user.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::InitiatePartialUnstake { amount }.data(),
+    }
+}
+
+fn cancel_unstake(setup: &Setup) -> Instruction {
+    Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::CancelUnstake {
+            staking_pool: setup.staking_pool,
+            stake_position: setup.stake_position,
+            user: setup.user.pubkey(),
+            position_token_account: None,
+        }
+        .to_account_metas(None),
+        data: instruction::CancelUnstake {}.data(),
+    }
+}
+
+fn early_unstake(setup: &Setup) -> Instruction {
+    Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::EarlyUnstake {
+            staking_pool: setup.staking_pool,
+            stake_mint: setup.stake_mint,
+            stake_vault: setup.stake_vault,
+            reward_vault: setup.reward_vault,
+            lock_tier_table: setup.lock_tier_table,
+            user_stake_account: setup.user_stake_account,
+            stake_position: setup.stake_position,
+            user: setup.user.pubkey(),
+            position_mint: None,
+            position_token_account: None,
+            user_token_account: setup.user_token_account,
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::EarlyUnstake {}.data(),
+    }
+}
+
+#[tokio::test]
+async fn unstake_waits_for_lock_then_unbonding() {
+    let mut setup = setup().await;
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+    let lock_end = position.lock_end;
+    assert_eq!(lock_end, position.stake_timestamp + LOCK_DURATION as i64);
+
+    // Still locked one second before the lock ends.
+    set_unix_timestamp(&mut setup.ctx, lock_end - 1).await;
+    let ix = initiate_unstake(&setup);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::LockPeriodNotComplete,
+    );
+
+    set_unix_timestamp(&mut setup.ctx, lock_end).await;
+    let ix = initiate_unstake(&setup);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+    assert!(position.is_unbonding);
+    assert_eq!(position.unbonding_end, lock_end + UNBONDING_PERIOD as i64);
+
+    // The cooldown runs from the unstake request, not from the stake.
+    set_unix_timestamp(&mut setup.ctx, position.unbonding_end - 1).await;
+    let ix = complete_unstake(&setup, setup.stake_position);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::UnbondingPeriodNotComplete,
+    );
+    assert_eq!(
+        token_balance(&mut setup.ctx, setup.user_token_account).await,
+        0
+    );
+
+    set_unix_timestamp(&mut setup.ctx, position.unbonding_end).await;
+    let ix = complete_unstake(&setup, setup.stake_position);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    assert_eq!(
+        token_balance(&mut setup.ctx, setup.user_token_account).await,
+        STAKE_AMOUNT
+    );
+    assert_eq!(token_balance(&mut setup.ctx, setup.stake_vault).await, 0);
+    assert!(!account_exists(&mut setup.ctx, setup.stake_position).await);
+}
+
+#[tokio::test]
+async fn slot_warp_does_not_shorten_lock_or_unbonding() {
+    let mut setup = setup().await;
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+
+    // Slots far past any lock length, with the clock's timestamp held back.
+    setup.ctx.warp_to_slot(10_000_000).unwrap();
+    set_unix_timestamp(&mut setup.ctx, position.stake_timestamp).await;
+    let ix = initiate_unstake(&setup);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::LockPeriodNotComplete,
+    );
+
+    set_unix_timestamp(&mut setup.ctx, position.lock_end).await;
+    let ix = initiate_unstake(&setup);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    setup.ctx.warp_to_slot(20_000_000).unwrap();
+    set_unix_timestamp(&mut setup.ctx, position.lock_end).await;
+    let ix = complete_unstake(&setup, setup.stake_position);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::UnbondingPeriodNotComplete,
+    );
+}
+
+#[tokio::test]
+async fn partial_unstake_waits_for_lock_then_unbonds_from_request() {
+    let mut setup = setup().await;
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+    let unbonding_position = stake_position_address(&setup.user.pubkey(), &setup.staking_pool, 1);
+
+    set_unix_timestamp(&mut setup.ctx, position.lock_end - 1).await;
+    let ix = initiate_partial_unstake(&setup, unbonding_position, STAKE_AMOUNT / 4);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::LockPeriodNotComplete,
+    );
+
+    let requested_at = position.lock_end + 86_400;
+    set_unix_timestamp(&mut setup.ctx, requested_at).await;
+    let ix = initiate_partial_unstake(&setup, unbonding_position, STAKE_AMOUNT / 4);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    let remaining = stake_position(&mut setup.ctx, setup.stake_position).await;
+    assert!(!remaining.is_unbonding);
+    let unbonding = stake_position(&mut setup.ctx, unbonding_position).await;
+    assert!(unbonding.is_unbonding);
+    assert_eq!(
+        unbonding.unbonding_end,
+        requested_at + UNBONDING_PERIOD as i64
+    );
+
+    set_unix_timestamp(&mut setup.ctx, unbonding.unbonding_end - 1).await;
+    let ix = complete_unstake(&setup, unbonding_position);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::UnbondingPeriodNotComplete,
+    );
+
+    set_unix_timestamp(&mut setup.ctx, unbonding.unbonding_end).await;
+    let ix = complete_unstake(&setup, unbonding_position);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut setup.ctx, setup.user_token_account).await,
+        STAKE_AMOUNT / 4
+    );
+}
+
+#[tokio::test]
+async fn cancelled_unstake_restarts_unbonding_on_the_next_request() {
+    let mut setup = setup().await;
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+
+    set_unix_timestamp(&mut setup.ctx, position.lock_end).await;
+    let ix = initiate_unstake(&setup);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    // Cancelled once the first cooldown has already run out.
+    let cancelled_at = position.lock_end + UNBONDING_PERIOD as i64;
+    set_unix_timestamp(&mut setup.ctx, cancelled_at).await;
+    let ix = cancel_unstake(&setup);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+    assert!(!position.is_unbonding);
+    assert_eq!(position.unbonding_end, 0);
+    let ix = complete_unstake(&setup, setup.stake_position);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::NotUnbonding,
+    );
+
+    // The second request repeats the first transaction, so it needs a new
+    // blockhash, and the new bank resets the clock.
+    setup.ctx.get_new_latest_blockhash().await.unwrap();
+    set_unix_timestamp(&mut setup.ctx, cancelled_at).await;
+    let ix = initiate_unstake(&setup);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+    assert_eq!(
+        position.unbonding_end,
+        cancelled_at + UNBONDING_PERIOD as i64
+    );
+    set_unix_timestamp(&mut setup.ctx, position.unbonding_end - 1).await;
+    let ix = complete_unstake(&setup, setup.stake_position);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::UnbondingPeriodNotComplete,
+    );
+}
+
+#[tokio::test]
+async fn early_unstake_penalty_follows_remaining_lock_time() {
+    let mut setup = setup().await;
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+
+    set_unix_timestamp(&mut setup.ctx, position.lock_end).await;
+    let ix = early_unstake(&setup);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::LockPeriodComplete,
+    );
+
+    // Half the lock remains by the clock, however many slots have passed.
+    setup.ctx.warp_to_slot(10_000_000).unwrap();
+    set_unix_timestamp(&mut setup.ctx, position.lock_end - LOCK_DURATION as i64 / 2).await;
+    let ix = early_unstake(&setup);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    let penalty = STAKE_AMOUNT * EARLY_UNSTAKE_MAX_PENALTY_BPS as u64 / 10_000 / 2;
+    assert_eq!(
+        token_balance(&mut setup.ctx, setup.user_token_account).await,
+        STAKE_AMOUNT - penalty
+    );
+    assert_eq!(
+        token_balance(&mut setup.ctx, setup.reward_vault).await,
+        penalty
+    );
+    assert!(!account_exists(&mut setup.ctx, setup.stake_position).await);
+}
+
+#[tokio::test]
+async fn liquid_unstake_unbonds_from_request() {
+    let mut setup = setup().await;
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+    let payer = setup.ctx.payer.pubkey();
+    let (receipt_mint, _) = Pubkey::find_program_address(
+        &[b"receipt_mint", setup.staking_pool.as_ref()],
+        &staking_program::ID,
+    );
+
+    let enable = Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::EnableLiquidStaking {
+            staking_pool: setup.staking_pool,
+            stake_mint: setup.stake_mint,
+            receipt_mint,
+            authority: payer,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+            rent: sysvar::rent::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::EnableLiquidStaking {
+            reward_multiplier: 10_000,
+        }
+        .data(),
+    };
+    send(&mut setup.ctx, &[enable], &[]).await.unwrap();
+    let user_receipt_account =
+        create_token_account(&mut setup.ctx, &receipt_mint, &setup.user.pubkey()).await;
+    mint_to(
+        &mut setup.ctx,
+        &setup.stake_mint,
+        &setup.user_token_account,
+        STAKE_AMOUNT,
+    )
+    .await;
+
+    let liquid_stake = Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::LiquidStake {
+            staking_pool: setup.staking_pool,
+            stake_vault: setup.stake_vault,
+            reward_vault: setup.reward_vault,
+            receipt_mint,
+            user_stake_account: setup.user_stake_account,
+            user: setup.user.pubkey(),
+            user_token_account: setup.user_token_account,
+            user_receipt_account,
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::LiquidStake {
+            amount: STAKE_AMOUNT,
+        }
+        .data(),
+    };
+    send(&mut setup.ctx, &[liquid_stake], &[&setup.user])
+        .await
+        .unwrap();
+    let receipt_amount = token_balance(&mut setup.ctx, user_receipt_account).await;
+
+    // Liquid stake has no lock, so it can leave while the tiered position is
+    // still locked.
+    let requested_at = position.stake_timestamp + 86_400;
+    set_unix_timestamp(&mut setup.ctx, requested_at).await;
+    let unbonding_position = stake_position_address(&setup.user.pubkey(), &setup.staking_pool, 1);
+    let liquid_unstake = Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::LiquidUnstake {
+            staking_pool: setup.staking_pool,
+            stake_vault: setup.stake_vault,
+            reward_vault: setup.reward_vault,
+            receipt_mint,
+            user_stake_account: setup.user_stake_account,
+            stake_position: unbonding_position,
+            user: setup.user.pubkey(),
+            user_receipt_account,
+            token_program: spl_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: instruction::LiquidUnstake { receipt_amount }.data(),
+    };
+    send(&mut setup.ctx, &[liquid_unstake], &[&setup.user])
+        .await
+        .unwrap();
+
+    let unbonding = stake_position(&mut setup.ctx, unbonding_position).await;
+    assert!(unbonding.is_unbonding);
+    assert_eq!(unbonding.lock_end, requested_at);
+    assert_eq!(
+        unbonding.unbonding_end,
+        requested_at + UNBONDING_PERIOD as i64
+    );
+
+    set_unix_timestamp(&mut setup.ctx, unbonding.unbonding_end - 1).await;
+    let ix = complete_unstake(&setup, unbonding_position);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::UnbondingPeriodNotComplete,
+    );
+
+    set_unix_timestamp(&mut setup.ctx, unbonding.unbonding_end).await;
+    let ix = complete_unstake(&setup, unbonding_position);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut setup.ctx, setup.user_token_account).await,
+        STAKE_AMOUNT
+    );
+}
+
+#[tokio::test]
+async fn sunset_lifts_the_lock_but_not_unbonding() {
+    let mut setup = setup().await;
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+
+    set_unix_timestamp(&mut setup.ctx, position.stake_timestamp).await;
+    let ix = initiate_unstake(&setup);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::LockPeriodNotComplete,
+    );
+
+    let sunset = Instruction {
+        program_id: staking_program::ID,
+        accounts: accounts::SetPoolState {
+            staking_pool: setup.staking_pool,
+            authority: setup.ctx.payer.pubkey(),
+        }
+        .to_account_metas(None),
+        data: instruction::SetPoolState {
+            state: PoolState::Sunset,
+        }
+        .data(),
+    };
+    send(&mut setup.ctx, &[sunset], &[]).await.unwrap();
+
+    let ix = initiate_unstake(&setup);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+
+    let position = stake_position(&mut setup.ctx, setup.stake_position).await;
+    assert_eq!(
+        position.unbonding_end,
+        position.stake_timestamp + UNBONDING_PERIOD as i64
+    );
+
+    set_unix_timestamp(&mut setup.ctx, position.unbonding_end - 1).await;
+    let ix = complete_unstake(&setup, setup.stake_position);
+    assert_error(
+        send(&mut setup.ctx, &[ix], &[&setup.user]).await,
+        ErrorCode::UnbondingPeriodNotComplete,
+    );
+
+    set_unix_timestamp(&mut setup.ctx, position.unbonding_end).await;
+    let ix = complete_unstake(&setup, setup.stake_position);
+    send(&mut setup.ctx, &[ix], &[&setup.user]).await.unwrap();
+    assert_eq!(
+        token_balance(&mut setup.ctx, setup.user_token_account).await,
+        STAKE_AMOUNT
+    );
+}
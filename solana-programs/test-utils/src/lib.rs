@@ -0,0 +1,166 @@
+//! Fixtures shared by the programs' `solana-program-test` suites.
+
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use anchor_spl::token::{spl_token, TokenAccount};
+use solana_program_test::{BanksClientError, ProgramTestContext};
+use solana_sdk::{
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+
+pub use solana_program_test;
+pub use solana_sdk;
+
+/// Builds a `ProgramTest` that runs an Anchor program natively, e.g.
+/// `program_test!(staking_program)`.
+#[macro_export]
+macro_rules! program_test {
+    ($program:ident) => {{
+        fn process_instruction(
+            program_id: &$crate::solana_sdk::pubkey::Pubkey,
+            accounts: &[$crate::solana_sdk::account_info::AccountInfo],
+            data: &[u8],
+        ) -> $crate::solana_sdk::entrypoint::ProgramResult {
+            // The Anchor entrypoint ties the slice lifetime to the account lifetime.
+            let accounts = Box::leak(Box::new(accounts.to_vec()));
+            $program::entry(program_id, accounts, data)
+        }
+        $crate::solana_program_test::ProgramTest::new(
+            stringify!($program),
+            $program::ID,
+            $crate::solana_program_test::processor!(process_instruction),
+        )
+    }};
+}
+
+pub async fn send(
+    ctx: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&ctx.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    ctx.banks_client.process_transaction(tx).await
+}
+
+/// Takes a program's `ErrorCode`, which converts to its on-chain code.
+pub fn assert_error(result: Result<(), BanksClientError>, error: impl Into<u32>) {
+    match result.unwrap_err().unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            assert_eq!(code, error.into())
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+}
+
+pub async fn set_unix_timestamp(ctx: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock: Clock = ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    ctx.set_sysvar(&clock);
+}
+
+pub async fn fetch_account<T: AccountDeserialize>(
+    ctx: &mut ProgramTestContext,
+    address: Pubkey,
+) -> T {
+    let account = ctx
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .unwrap();
+    T::try_deserialize(&mut &account.data[..]).unwrap()
+}
+
+pub async fn account_exists(ctx: &mut ProgramTestContext, address: Pubkey) -> bool {
+    ctx.banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .is_some()
+}
+
+pub async fn token_balance(ctx: &mut ProgramTestContext, address: Pubkey) -> u64 {
+    fetch_account::<TokenAccount>(ctx, address).await.amount
+}
+
+/// A fresh keypair holding enough lamports to pay for its own accounts.
+pub async fn funded_keypair(ctx: &mut ProgramTestContext) -> Keypair {
+    let keypair = Keypair::new();
+    let ix = system_instruction::transfer(&ctx.payer.pubkey(), &keypair.pubkey(), 1_000_000_000);
+    send(ctx, &[ix], &[]).await.unwrap();
+    keypair
+}
+
+/// Creates a mint whose mint authority is the context payer.
+pub async fn create_mint(ctx: &mut ProgramTestContext, decimals: u8) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let payer = ctx.payer.pubkey();
+    let instructions = [
+        system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &payer,
+            None,
+            decimals,
+        )
+        .unwrap(),
+    ];
+    send(ctx, &instructions, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(
+    ctx: &mut ProgramTestContext,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Pubkey {
+    let account = Keypair::new();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &ctx.payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_account(&spl_token::ID, &account.pubkey(), mint, owner)
+            .unwrap(),
+    ];
+    send(ctx, &instructions, &[&account]).await.unwrap();
+    account.pubkey()
+}
+
+/// Mints from a mint created by `create_mint`.
+pub async fn mint_to(ctx: &mut ProgramTestContext, mint: &Pubkey, account: &Pubkey, amount: u64) {
+    let ix = spl_token::instruction::mint_to(
+        &spl_token::ID,
+        mint,
+        account,
+        &ctx.payer.pubkey(),
+        &[],
+        amount,
+    )
+    .unwrap();
+    send(ctx, &[ix], &[]).await.unwrap();
+}